@@ -1,8 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::time::Duration;
 
 use ash_term::event::{Event, KeyCode, KeyEvent, Modifiers};
 use maplit::hashmap;
 
+use crate::document::Motion;
 use crate::editor::Mode;
 
 #[derive(Debug, Clone)]
@@ -11,31 +15,329 @@ pub enum Action {
 
     InsertChar(char),
     InsertCharAfter(char),
+    /// Insert a newline, keeping the current line's indentation.
+    InsertNewline,
+    /// Insert a tab, or spaces if indenting with spaces.
+    InsertTab,
+
+    ReplaceChar(char),
 
     InsertString(String),
+    #[allow(dead_code)]
     InsertStringAfter(String),
 
     Backspace,
     Delete,
 
+    DuplicateLine,
+    DeleteLine,
+    JoinLines,
+    Indent,
+    Dedent,
+
+    /// Add to the number under or after the cursor.
+    ModifyNumber(i64),
+
+    Move(Motion),
+
+    // Shorthands for common motions.
     MoveLeft,
     MoveRight,
     MoveUp,
     MoveDown,
-
     MoveHome,
     MoveEnd,
 
+    /// Go to the start of a line, counting from zero.
+    GoToLine(usize),
+
+    PageUp,
+    PageDown,
+
+    CenterCursor,
+
+    SelectWord,
+    ClearSelection,
+
+    ToggleLineNumbers,
+    ToggleWrap,
+    SetLineNumbers(bool),
+    SetWrap(bool),
+    ToggleAutoPairs,
+    /// Toggle whether the cursor can move past the end of lines in the
+    /// current document.
+    ToggleVirtualEdit,
+    /// Set the width of a tab stop in the active document.
+    SetTabWidth(usize),
+    /// Set whether the active document is indented with spaces, as many as
+    /// its tab width, rather than tabs.
+    SetExpandTab(bool),
+    /// Set whether the active document can be edited.
+    SetReadOnly(bool),
+    /// Set whether saving the active document adds a final newline if there
+    /// isn't one.
+    SetFixEol(bool),
+
+    NextBuffer,
+    PrevBuffer,
+
+    Split,
+    CloseSplits,
+
     SetMode(Mode),
 
+    CommandInsert(char),
+    CommandBackspace,
+    CommandExecute,
+
+    CancelSearch,
+    SearchNext,
+    #[cfg(feature = "regex")]
+    SearchRegex(String),
+
+    /// Replace every occurrence of the first string with the second.
+    ReplaceAll(String, String),
+
+    ShowStats,
+    /// Toggle an overlay listing the key bindings.
+    ShowHelp,
+
+    /// Repeat the last change.
+    RepeatChange,
+
+    SetMark(char),
+    JumpToMark(char),
+
+    /// Start recording keys into a macro register.
+    RecordMacro(char),
+    /// Replay the keys recorded in a macro register.
+    PlayMacro(char),
+
     Save,
+    /// Re-read the document from disk, if it has no unsaved changes.
+    Reload,
+    /// Re-read the document from disk, discarding any unsaved changes.
+    ForceReload,
+    /// Save every modified document that has a path.
+    AutoSave,
+    /// Set how long to wait after the last input before autosaving, or
+    /// disable autosave.
+    SetAutosave(Option<Duration>),
+
     Quit,
+    ForceQuit,
+
+    /// Disables bindings for the same keys in lower priority maps.
+    Unbound,
+}
+
+impl Action {
+    /// The motion this action moves the cursor by, if it's a motion.
+    pub fn motion(&self) -> Option<Motion> {
+        match self {
+            Self::Move(motion) => Some(*motion),
+
+            Self::MoveLeft => Some(Motion::CharLeft),
+            Self::MoveRight => Some(Motion::CharRight),
+            Self::MoveUp => Some(Motion::LineUp),
+            Self::MoveDown => Some(Motion::LineDown),
+            Self::MoveHome => Some(Motion::LineStart),
+            Self::MoveEnd => Some(Motion::LineEnd),
+
+            _ => None,
+        }
+    }
+
+    /// Whether this action changes the text of the document.
+    pub fn is_edit(&self) -> bool {
+        match self {
+            Self::Combo(actions) => actions.iter().any(Self::is_edit),
+
+            Self::InsertChar(_)
+            | Self::InsertCharAfter(_)
+            | Self::InsertNewline
+            | Self::InsertTab
+            | Self::ReplaceChar(_)
+            | Self::InsertString(_)
+            | Self::InsertStringAfter(_)
+            | Self::Backspace
+            | Self::Delete
+            | Self::DuplicateLine
+            | Self::DeleteLine
+            | Self::JoinLines
+            | Self::Indent
+            | Self::Dedent
+            | Self::ModifyNumber(_)
+            | Self::ReplaceAll(..) => true,
+
+            _ => false,
+        }
+    }
+}
+
+/// A short description, for listing bindings.
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(motion) = self.motion() {
+            let name = match motion {
+                Motion::CharLeft => "left",
+                Motion::CharRight => "right",
+                Motion::LineUp => "up",
+                Motion::LineDown => "down",
+                Motion::WordLeft => "previous word",
+                Motion::WordRight => "next word",
+                Motion::LineStart => "start of line",
+                Motion::SmartLineStart => "start of text or line",
+                Motion::LineEnd => "end of line",
+                Motion::DocumentStart => "start of document",
+                Motion::DocumentEnd => "end of document",
+            };
+            return write!(f, "move {name}");
+        }
+
+        match self {
+            Self::Combo(actions) => {
+                for (i, action) in actions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", then ")?;
+                    }
+                    write!(f, "{action}")?;
+                }
+                Ok(())
+            }
+
+            Self::InsertChar(ch) => write!(f, "insert {ch:?}"),
+            Self::InsertCharAfter(ch) => write!(f, "insert {ch:?} after the cursor"),
+            Self::InsertNewline => write!(f, "insert newline"),
+            Self::InsertTab => write!(f, "insert tab"),
+            Self::ReplaceChar(ch) => write!(f, "replace with {ch:?}"),
+            Self::InsertString(s) => write!(f, "insert {s:?}"),
+            Self::InsertStringAfter(s) => write!(f, "insert {s:?} after the cursor"),
+
+            Self::Backspace => write!(f, "delete backwards"),
+            Self::Delete => write!(f, "delete"),
+
+            Self::DuplicateLine => write!(f, "duplicate line"),
+            Self::DeleteLine => write!(f, "delete line"),
+            Self::JoinLines => write!(f, "join lines"),
+            Self::Indent => write!(f, "indent line"),
+            Self::Dedent => write!(f, "dedent line"),
+
+            Self::ModifyNumber(delta) if *delta < 0 => {
+                write!(f, "decrement number by {}", delta.unsigned_abs())
+            }
+            Self::ModifyNumber(delta) => write!(f, "increment number by {delta}"),
+
+            Self::GoToLine(line) => write!(f, "go to line {}", line + 1),
+
+            Self::PageUp => write!(f, "page up"),
+            Self::PageDown => write!(f, "page down"),
+
+            Self::CenterCursor => write!(f, "center cursor"),
+
+            Self::SelectWord => write!(f, "select word"),
+            Self::ClearSelection => write!(f, "clear selection"),
+
+            Self::ToggleLineNumbers => write!(f, "toggle line numbers"),
+            Self::ToggleWrap => write!(f, "toggle wrapping"),
+            Self::SetLineNumbers(true) => write!(f, "show line numbers"),
+            Self::SetLineNumbers(false) => write!(f, "hide line numbers"),
+            Self::SetWrap(true) => write!(f, "wrap lines"),
+            Self::SetWrap(false) => write!(f, "don't wrap lines"),
+            Self::ToggleAutoPairs => write!(f, "toggle auto pairs"),
+            Self::ToggleVirtualEdit => write!(f, "toggle virtual editing"),
+            Self::SetTabWidth(width) => write!(f, "set tab width to {width}"),
+            Self::SetExpandTab(true) => write!(f, "indent with spaces"),
+            Self::SetExpandTab(false) => write!(f, "indent with tabs"),
+            Self::SetReadOnly(true) => write!(f, "make read-only"),
+            Self::SetReadOnly(false) => write!(f, "make editable"),
+            Self::SetFixEol(true) => write!(f, "add a final newline on save"),
+            Self::SetFixEol(false) => write!(f, "save the final newline as it is"),
+
+            Self::NextBuffer => write!(f, "next buffer"),
+            Self::PrevBuffer => write!(f, "previous buffer"),
+
+            Self::Split => write!(f, "split"),
+            Self::CloseSplits => write!(f, "close splits"),
+
+            Self::SetMode(mode) => write!(f, "{} mode", format!("{mode:?}").to_lowercase()),
+
+            Self::CommandInsert(ch) => write!(f, "type {ch:?}"),
+            Self::CommandBackspace => write!(f, "delete backwards"),
+            Self::CommandExecute => write!(f, "execute"),
+
+            Self::CancelSearch => write!(f, "cancel search"),
+            Self::SearchNext => write!(f, "next match"),
+            #[cfg(feature = "regex")]
+            Self::SearchRegex(pattern) => write!(f, "search for /{pattern}/"),
+
+            Self::ReplaceAll(from, to) => write!(f, "replace {from:?} with {to:?}"),
+
+            Self::ShowStats => write!(f, "show stats"),
+            Self::ShowHelp => write!(f, "show help"),
+
+            Self::RepeatChange => write!(f, "repeat last change"),
+
+            Self::SetMark(name) => write!(f, "set mark {name}"),
+            Self::JumpToMark(name) => write!(f, "jump to mark {name}"),
+
+            Self::RecordMacro(register) => write!(f, "record macro {register}"),
+            Self::PlayMacro(register) => write!(f, "play macro {register}"),
+
+            Self::Save => write!(f, "save"),
+            Self::Reload => write!(f, "reload"),
+            Self::ForceReload => write!(f, "reload, discarding changes"),
+            Self::AutoSave => write!(f, "autosave"),
+            Self::SetAutosave(Some(interval)) => {
+                write!(f, "autosave after {}s", interval.as_secs())
+            }
+            Self::SetAutosave(None) => write!(f, "disable autosave"),
+
+            Self::Quit => write!(f, "quit"),
+            Self::ForceQuit => write!(f, "quit, discarding changes"),
+
+            Self::Unbound => write!(f, "unbound"),
+
+            Self::Move(_)
+            | Self::MoveLeft
+            | Self::MoveRight
+            | Self::MoveUp
+            | Self::MoveDown
+            | Self::MoveHome
+            | Self::MoveEnd => unreachable!("motions are handled above"),
+        }
+    }
+}
+
+/// The names marks can be set with.
+pub const MARK_NAMES: RangeInclusive<char> = 'a'..='z';
+
+/// The registers macros can be recorded into.
+pub const MACRO_REGISTERS: RangeInclusive<char> = 'a'..='z';
+
+pub type Bindings = HashMap<Vec<KeyEvent>, Action>;
+
+/// Identifies one of the maps in a [`KeyMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapId {
+    All,
+    Normal,
+    Insert,
+    Replace,
+    Command,
+    Search,
 }
 
 pub struct KeyMap {
-    pub all: HashMap<KeyEvent, Action>,
-    pub normal: HashMap<KeyEvent, Action>,
-    pub insert: HashMap<KeyEvent, Action>,
+    pub all: Bindings,
+    pub normal: Bindings,
+    pub insert: Bindings,
+    pub replace: Bindings,
+    pub command: Bindings,
+    pub search: Bindings,
+
+    /// The maps to look up bindings in for each mode, highest priority first.
+    pub lookup_order: HashMap<Mode, Vec<MapId>>,
 }
 
 impl Default for KeyMap {
@@ -47,86 +349,432 @@ impl Default for KeyMap {
 impl KeyMap {
     pub fn basic() -> Self {
         let all = hashmap! {
-            KeyEvent::new(KeyCode::Left) => Action::MoveLeft,
-            KeyEvent::new(KeyCode::Right) => Action::MoveRight,
-            KeyEvent::new(KeyCode::Up) => Action::MoveUp,
-            KeyEvent::new(KeyCode::Down) => Action::MoveDown,
+            key(KeyCode::Left) => Action::MoveLeft,
+            key(KeyCode::Right) => Action::MoveRight,
+            key(KeyCode::Up) => Action::MoveUp,
+            key(KeyCode::Down) => Action::MoveDown,
+
+            key(KeyCode::Home) => Action::Move(Motion::SmartLineStart),
+            key(KeyCode::End) => Action::MoveEnd,
+
+            key(KeyCode::PageUp) => Action::PageUp,
+            key(KeyCode::PageDown) => Action::PageDown,
 
-            KeyEvent::new(KeyCode::Home) => Action::MoveHome,
-            KeyEvent::new(KeyCode::End) => Action::MoveEnd,
+            key(KeyCode::Fn(1)) => Action::ShowHelp,
         };
 
-        let normal = hashmap! {
-            KeyEvent::new(KeyCode::Char('i')) => Action::SetMode(Mode::Insert),
+        let mut normal = hashmap! {
+            keys("i") => Action::SetMode(Mode::Insert),
+            keys("R") => Action::SetMode(Mode::Replace),
+            keys(":") => Action::SetMode(Mode::Command),
+            keys("/") => Action::SetMode(Mode::Search),
+            keys("n") => Action::SearchNext,
 
-            KeyEvent::new(KeyCode::Char('o')) => Action::Combo(vec![
+            keys("o") => Action::Combo(vec![
                 Action::MoveEnd,
                 Action::InsertChar('\n'),
                 Action::SetMode(Mode::Insert),
             ]),
-            KeyEvent::new(KeyCode::Char('O')) => Action::Combo(vec![
+            keys("O") => Action::Combo(vec![
                 Action::MoveHome,
                 Action::InsertCharAfter('\n'),
                 Action::SetMode(Mode::Insert),
             ]),
 
-            KeyEvent::new(KeyCode::Char('d')) => Action::Delete,
+            keys("x") => Action::Delete,
+            keys(".") => Action::RepeatChange,
 
-            KeyEvent::new(KeyCode::Char('h')) => Action::MoveLeft,
-            KeyEvent::new(KeyCode::Char('l')) => Action::MoveRight,
-            KeyEvent::new(KeyCode::Char('k')) => Action::MoveUp,
-            KeyEvent::new(KeyCode::Char('j')) => Action::MoveDown,
+            keys("yyp") => Action::DuplicateLine,
+            keys("dd") => Action::DeleteLine,
+            keys("J") => Action::JoinLines,
+            keys(">>") => Action::Indent,
+            keys("<<") => Action::Dedent,
+            key(KeyCode::Tab) => Action::Indent,
+            shift(KeyCode::Tab) => Action::Dedent,
 
-            KeyEvent::new(KeyCode::Char('s')) => Action::Save,
-            KeyEvent::new(KeyCode::Char('q')) => Action::Quit,
+            ctrl('A') => Action::ModifyNumber(1),
+            ctrl('X') => Action::ModifyNumber(-1),
+
+            keys("h") => Action::MoveLeft,
+            keys("l") => Action::MoveRight,
+            keys("k") => Action::MoveUp,
+            keys("j") => Action::MoveDown,
+
+            keys("w") => Action::Move(Motion::WordRight),
+            keys("b") => Action::Move(Motion::WordLeft),
+
+            keys("gg") => Action::Move(Motion::DocumentStart),
+            keys("G") => Action::Move(Motion::DocumentEnd),
+
+            keys("zz") => Action::CenterCursor,
+
+            keys("viw") => Action::SelectWord,
+            key(KeyCode::Escape) => Action::ClearSelection,
+
+            keys("\\n") => Action::ToggleLineNumbers,
+            keys("\\w") => Action::ToggleWrap,
+            keys("\\p") => Action::ToggleAutoPairs,
+            keys("\\v") => Action::ToggleVirtualEdit,
+
+            vec![
+                KeyEvent::new(KeyCode::Char('g')),
+                KeyEvent::new_with_mods(KeyCode::Char('G'), Modifiers::CTRL),
+            ] => Action::ShowStats,
+
+            keys("s") => Action::Save,
         };
 
+        for name in MARK_NAMES {
+            normal.insert(keys(&format!("m{name}")), Action::SetMark(name));
+            normal.insert(keys(&format!("`{name}")), Action::JumpToMark(name));
+        }
+
+        for register in MACRO_REGISTERS {
+            normal.insert(keys(&format!("q{register}")), Action::RecordMacro(register));
+            normal.insert(keys(&format!("@{register}")), Action::PlayMacro(register));
+        }
+
         let insert = hashmap! {
-            KeyEvent::new(KeyCode::Backspace) => Action::Backspace,
-            KeyEvent::new(KeyCode::Delete) => Action::Delete,
-            KeyEvent::new(KeyCode::Escape) => Action::SetMode(Mode::Normal),
+            key(KeyCode::Tab) => Action::InsertTab,
+            shift(KeyCode::Tab) => Action::Dedent,
+            key(KeyCode::Backspace) => Action::Backspace,
+            key(KeyCode::Delete) => Action::Delete,
+            key(KeyCode::Escape) => Action::SetMode(Mode::Normal),
+        };
+
+        let replace = hashmap! {
+            key(KeyCode::Backspace) => Action::MoveLeft,
+            key(KeyCode::Delete) => Action::Delete,
+            key(KeyCode::Escape) => Action::SetMode(Mode::Normal),
+        };
+
+        // Don't move around the document while typing a command or search.
+        let disabled = all.keys().map(|keys| (keys.clone(), Action::Unbound));
+
+        let mut command = hashmap! {
+            key(KeyCode::Backspace) => Action::CommandBackspace,
+            key(KeyCode::Return) => Action::CommandExecute,
+            key(KeyCode::Escape) => Action::SetMode(Mode::Normal),
+        };
+        command.extend(disabled.clone());
+
+        let mut search = hashmap! {
+            key(KeyCode::Backspace) => Action::CommandBackspace,
+            key(KeyCode::Return) => Action::CommandExecute,
+            key(KeyCode::Escape) => Action::CancelSearch,
         };
+        search.extend(disabled);
 
         Self {
             all,
             normal,
             insert,
+            replace,
+            command,
+            search,
+
+            lookup_order: hashmap! {
+                Mode::Normal => vec![MapId::Normal, MapId::All],
+                Mode::Insert => vec![MapId::Insert, MapId::All],
+                Mode::Replace => vec![MapId::Replace, MapId::All],
+                Mode::Command => vec![MapId::Command, MapId::All],
+                Mode::Search => vec![MapId::Search, MapId::All],
+            },
+        }
+    }
+
+    pub fn map(&self, id: MapId) -> &Bindings {
+        match id {
+            MapId::All => &self.all,
+            MapId::Normal => &self.normal,
+            MapId::Insert => &self.insert,
+            MapId::Replace => &self.replace,
+            MapId::Command => &self.command,
+            MapId::Search => &self.search,
         }
     }
 
-    pub fn get_action(&self, mode: Mode, event: Event) -> Option<Action> {
+    /// The bindings in effect in a mode, sorted by their keys. Bindings
+    /// shadowed by a higher priority map, or unbound in one, are left out.
+    pub fn bindings_for_mode(&self, mode: Mode) -> Vec<(&[KeyEvent], &Action)> {
+        let mut seen = HashSet::new();
+        let mut bindings = vec![];
+
+        for &id in self.lookup_order.get(&mode).into_iter().flatten() {
+            for (keys, action) in self.map(id) {
+                if seen.insert(keys) && !matches!(action, Action::Unbound) {
+                    bindings.push((keys.as_slice(), action));
+                }
+            }
+        }
+
+        bindings.sort_by_cached_key(|(keys, _)| format_keys(keys));
+        bindings
+    }
+
+    /// Get the action for an event, given the keys pressed so far.
+    ///
+    /// Key events are accumulated in `pending` while they form the prefix of a
+    /// longer binding, and `pending` is cleared once a binding is matched or
+    /// can no longer be matched. Keys bound to [`Action::Unbound`] give that
+    /// action, so they can be told apart from keys with no binding at all.
+    pub fn get_action(
+        &self,
+        mode: Mode,
+        pending: &mut Vec<KeyEvent>,
+        event: Event,
+    ) -> Option<Action> {
+        let key = match event {
+            Event::Paste(_) if matches!(mode, Mode::Command | Mode::Search) => return None,
+            Event::Paste(s) => {
+                pending.clear();
+                return Some(Action::InsertString(s));
+            }
+            Event::Key(key) => key,
+            _ => return None,
+        };
+
         match mode {
-            Mode::Normal => match event {
-                Event::Paste(s) => Some(Action::InsertString(s)),
-                Event::Key(key) => self
-                    .normal
-                    .get(&key)
-                    .cloned()
-                    .or_else(|| self.all.get(&key).cloned()),
-                _ => None,
-            },
+            Mode::Normal => {}
 
-            Mode::Insert => match event {
-                Event::Paste(s) => Some(Action::InsertString(s)),
+            Mode::Insert => {
+                if pending.is_empty() {
+                    match key {
+                        KeyEvent {
+                            key_code: KeyCode::Char(ch),
+                            modifiers: Modifiers::EMPTY,
+                        } => return Some(Action::InsertChar(ch)),
 
-                Event::Key(KeyEvent {
-                    key_code: KeyCode::Char(ch),
-                    modifiers: Modifiers::EMPTY,
-                }) => Some(Action::InsertChar(ch)),
+                        KeyEvent {
+                            key_code: KeyCode::Return,
+                            modifiers: Modifiers::EMPTY,
+                        } => return Some(Action::InsertNewline),
 
-                Event::Key(KeyEvent {
-                    key_code: KeyCode::Return,
-                    modifiers: Modifiers::EMPTY,
-                }) => Some(Action::InsertChar('\n')),
+                        _ => {}
+                    }
+                }
+            }
 
-                Event::Key(key) => self
-                    .insert
-                    .get(&key)
-                    .cloned()
-                    .or_else(|| self.all.get(&key).cloned()),
+            Mode::Replace => {
+                if pending.is_empty() {
+                    match key {
+                        KeyEvent {
+                            key_code: KeyCode::Char(ch),
+                            modifiers: Modifiers::EMPTY,
+                        } => return Some(Action::ReplaceChar(ch)),
 
-                _ => None,
-            },
+                        KeyEvent {
+                            key_code: KeyCode::Return,
+                            modifiers: Modifiers::EMPTY,
+                        } => return Some(Action::InsertChar('\n')),
+
+                        _ => {}
+                    }
+                }
+            }
+
+            Mode::Command | Mode::Search => {
+                if pending.is_empty() {
+                    if let KeyEvent {
+                        key_code: KeyCode::Char(ch),
+                        modifiers: Modifiers::EMPTY,
+                    } = key
+                    {
+                        return Some(Action::CommandInsert(ch));
+                    }
+                }
+            }
+        }
+
+        let maps: Vec<&Bindings> = self
+            .lookup_order
+            .get(&mode)
+            .into_iter()
+            .flatten()
+            .map(|&id| self.map(id))
+            .collect();
+
+        pending.push(key);
+
+        let is_prefix =
+            |keys: &Vec<KeyEvent>| keys.len() > pending.len() && keys.starts_with(pending);
+        if maps.iter().flat_map(|map| map.keys()).any(is_prefix) {
+            return None;
         }
+
+        let action = maps.iter().find_map(|map| map.get(pending)).cloned();
+        pending.clear();
+
+        action
+    }
+}
+
+/// Format a key sequence in Vim's notation, like `g<C-g>`.
+pub fn format_keys(keys: &[KeyEvent]) -> String {
+    keys.iter().map(KeyEvent::to_string).collect()
+}
+
+fn key(key_code: KeyCode) -> Vec<KeyEvent> {
+    vec![KeyEvent::new(key_code)]
+}
+
+/// A key pressed with shift.
+fn shift(key_code: KeyCode) -> Vec<KeyEvent> {
+    vec![KeyEvent::new_with_mods(key_code, Modifiers::SHIFT)]
+}
+
+/// A letter pressed with control, which terminals report in uppercase.
+fn ctrl(ch: char) -> Vec<KeyEvent> {
+    vec![KeyEvent::new_with_mods(KeyCode::Char(ch), Modifiers::CTRL)]
+}
+
+fn keys(s: &str) -> Vec<KeyEvent> {
+    s.chars()
+        .map(|ch| KeyEvent::new(KeyCode::Char(ch)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ash_term::event::{Event, KeyCode};
+
+    use super::{format_keys, key, keys, Action, KeyMap, MapId};
+    use crate::editor::Mode;
+
+    #[test]
+    fn key_sequence() {
+        let keymap = KeyMap::basic();
+        let mut pending = vec![];
+
+        let z = Event::key_no_mods(KeyCode::Char('z'));
+
+        assert!(keymap
+            .get_action(Mode::Normal, &mut pending, z.clone())
+            .is_none());
+        assert_eq!(pending.len(), 1);
+
+        assert!(matches!(
+            keymap.get_action(Mode::Normal, &mut pending, z),
+            Some(Action::CenterCursor)
+        ));
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn key_sequence_no_match() {
+        let keymap = KeyMap::basic();
+        let mut pending = vec![];
+
+        let z = Event::key_no_mods(KeyCode::Char('z'));
+        let x = Event::key_no_mods(KeyCode::Char('x'));
+
+        assert!(keymap.get_action(Mode::Normal, &mut pending, z).is_none());
+        assert!(keymap.get_action(Mode::Normal, &mut pending, x).is_none());
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn mode_binding_overrides_all() {
+        let mut keymap = KeyMap::basic();
+        keymap.normal.insert(key(KeyCode::Left), Action::MoveHome);
+
+        let left = Event::key_no_mods(KeyCode::Left);
+        let mut pending = vec![];
+
+        assert!(matches!(
+            keymap.get_action(Mode::Normal, &mut pending, left.clone()),
+            Some(Action::MoveHome)
+        ));
+        assert!(matches!(
+            keymap.get_action(Mode::Insert, &mut pending, left.clone()),
+            Some(Action::MoveLeft)
+        ));
+
+        // Give global bindings priority in normal mode.
+        keymap
+            .lookup_order
+            .insert(Mode::Normal, vec![MapId::All, MapId::Normal]);
+        assert!(matches!(
+            keymap.get_action(Mode::Normal, &mut pending, left),
+            Some(Action::MoveLeft)
+        ));
+    }
+
+    #[test]
+    fn disabled_binding() {
+        let mut keymap = KeyMap::basic();
+        keymap.insert.insert(key(KeyCode::Left), Action::Unbound);
+
+        let left = Event::key_no_mods(KeyCode::Left);
+        let mut pending = vec![];
+
+        assert!(matches!(
+            keymap.get_action(Mode::Insert, &mut pending, left.clone()),
+            Some(Action::Unbound)
+        ));
+        assert!(pending.is_empty());
+
+        assert!(matches!(
+            keymap.get_action(Mode::Normal, &mut pending, left.clone()),
+            Some(Action::MoveLeft)
+        ));
+
+        // Global motions are disabled in command mode by default.
+        assert!(matches!(
+            keymap.get_action(Mode::Command, &mut pending, left),
+            Some(Action::Unbound)
+        ));
+    }
+
+    #[test]
+    fn bindings_for_mode() {
+        let keymap = KeyMap::basic();
+        let bindings = keymap.bindings_for_mode(Mode::Normal);
+
+        let find = |keys: Vec<_>| {
+            bindings
+                .iter()
+                .find(|(k, _)| *k == keys.as_slice())
+                .map(|(_, action)| *action)
+        };
+
+        for ch in ['h', 'j', 'k', 'l'] {
+            assert!(find(keys(&ch.to_string())).is_some_and(|a| a.motion().is_some()));
+        }
+        assert!(matches!(
+            find(keys("i")),
+            Some(Action::SetMode(Mode::Insert))
+        ));
+        assert!(matches!(find(key(KeyCode::Left)), Some(Action::MoveLeft)));
+
+        let formatted: Vec<_> = bindings.iter().map(|(k, _)| format_keys(k)).collect();
+        assert!(formatted.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn bindings_for_mode_unbound() {
+        let keymap = KeyMap::basic();
+
+        // The arrow keys are unbound while typing a command.
+        let bindings = keymap.bindings_for_mode(Mode::Command);
+        assert!(!bindings.iter().any(|(k, _)| *k == key(KeyCode::Left)));
+        assert!(keymap
+            .bindings_for_mode(Mode::Normal)
+            .iter()
+            .any(|(k, _)| *k == key(KeyCode::Left)));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Action::MoveLeft.to_string(), "move left");
+        assert_eq!(Action::SetMode(Mode::Insert).to_string(), "insert mode");
+        assert_eq!(
+            Action::ModifyNumber(-1).to_string(),
+            "decrement number by 1"
+        );
+        assert_eq!(
+            Action::Combo(vec![Action::MoveEnd, Action::InsertChar('x')]).to_string(),
+            "move end of line, then insert 'x'"
+        );
     }
 }
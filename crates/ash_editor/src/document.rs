@@ -1,17 +1,20 @@
 use std::{
     borrow::Cow,
+    collections::HashMap,
+    fmt::{self, Write as _},
     fs::{self, File},
-    io::{BufWriter, Write},
-    ops::ControlFlow,
-    path::PathBuf,
+    hash::{DefaultHasher, Hasher},
+    io::{BufWriter, Read, Write},
+    ops::{Bound, ControlFlow, Range, RangeBounds, RangeInclusive},
+    path::{Path, PathBuf},
 };
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use ash_term::units::OffsetUsize;
-use crop::{Rope, RopeSlice};
+use crop::{Rope, RopeBuilder, RopeSlice};
 
-#[derive(Default)]
 pub struct Document {
     rope: Rope,
     path: Option<PathBuf>,
@@ -19,271 +22,3509 @@ pub struct Document {
     /// Cursor position, as a byte index.
     cursor_index: usize,
 
-    /// Column to try to move to when moving (in cells).
+    /// The other end of the selection from the cursor, as a byte index.
+    selection_anchor: Option<usize>,
+
+    /// Column to try to move to when moving (in cells). `usize::MAX` keeps
+    /// the cursor at the end of each line.
     target_column: Option<usize>,
 
-    /// Scroll offset, in cells.
+    /// Whether the cursor can move past the end of a line.
+    virtual_edit: bool,
+    /// How many cells past the end of the line the cursor is, with
+    /// `virtual_edit`. These are filled with spaces when text is inserted.
+    virtual_columns: usize,
+
+    /// Scroll offset, in cells, of the focused view of the document. It's
+    /// kept while other documents are shown, and panes that aren't focused
+    /// have their own.
     scroll_offset: OffsetUsize,
+
+    /// Width of a tab stop, in cells.
+    tab_width: usize,
+    /// What indentation is made of, when inserted by the editor.
+    indent_style: IndentStyle,
+
+    final_newline: FinalNewline,
+
+    /// Whether there are changes since the document was opened or saved.
+    modified: bool,
+
+    /// Hash of the content when the document was opened or last saved.
+    saved_hash: Option<u64>,
+
+    /// Whether the document can't be edited or saved.
+    read_only: bool,
+    /// Whether the file isn't text and is shown as a hex dump. Saving it would
+    /// write the dump over the file, so it stays read-only.
+    hex_dump: bool,
+
+    /// Named marks, as byte indices.
+    marks: HashMap<char, usize>,
+
+    /// Diagnostics from an external source, such as a language server.
+    diagnostics: Vec<Diagnostic>,
+
+    /// Called with each edit once it has been applied.
+    on_change: Option<ChangeHook>,
+    /// Edits that haven't been passed to `on_change` yet.
+    pending_edits: Vec<Edit>,
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self {
+            rope: Rope::new(),
+            path: None,
+
+            cursor_index: 0,
+            selection_anchor: None,
+            target_column: None,
+
+            virtual_edit: false,
+            virtual_columns: 0,
+
+            scroll_offset: OffsetUsize::ZERO,
+
+            tab_width: DEFAULT_TAB_WIDTH,
+            indent_style: IndentStyle::default(),
+
+            final_newline: FinalNewline::default(),
+
+            modified: false,
+            saved_hash: None,
+
+            read_only: false,
+            hex_dump: false,
+
+            marks: HashMap::new(),
+            diagnostics: vec![],
+
+            on_change: None,
+            pending_edits: vec![],
+        }
+    }
+}
+
+type ChangeHook = Box<dyn FnMut(&Edit)>;
+
+/// A change to the text of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    /// The byte range that was replaced, before the edit.
+    pub range: Range<usize>,
+    /// The text that replaced it.
+    pub text: String,
+}
+
+/// A way of moving the cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    CharLeft,
+    CharRight,
+    LineUp,
+    LineDown,
+
+    /// To the start of the previous word.
+    WordLeft,
+    /// To the start of the next word.
+    WordRight,
+
+    LineStart,
+    /// To the first non-blank character of the line, or to the start of the
+    /// line if already there.
+    SmartLineStart,
+    LineEnd,
+
+    DocumentStart,
+    DocumentEnd,
+}
+
+/// What a level of indentation is made of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndentStyle {
+    /// A number of spaces.
+    Spaces(usize),
+    /// A tab.
+    Tabs,
+}
+
+impl Default for IndentStyle {
+    fn default() -> Self {
+        Self::Spaces(DEFAULT_TAB_WIDTH)
+    }
+}
+
+/// A problem with a range of the text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The byte range the diagnostic covers.
+    pub range: Range<usize>,
+    pub severity: Severity,
+}
+
+/// How serious a diagnostic is, from most to least severe.
+#[allow(
+    dead_code,
+    reason = "set by a diagnostics source, such as a language client"
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+/// Counts over the whole document.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DocStats {
+    pub bytes: usize,
+    pub chars: usize,
+    /// Runs of non-whitespace characters.
+    pub words: usize,
+    pub lines: usize,
+}
+
+/// What to do with the final newline when saving.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinalNewline {
+    /// Save the text exactly as it is.
+    #[default]
+    Preserve,
+    /// Add a final newline if there isn't one.
+    Ensure,
 }
 
 impl Document {
     pub fn new(path: Option<PathBuf>) -> Result<Self> {
-        let rope = if let Some(path) = &path {
-            // TODO: do this properly
-            let source = fs::read_to_string(path).context("couldn't read file")?;
-            Rope::from(source)
+        let (rope, hex_dump) = if let Some(path) = &path {
+            let len = fs::metadata(path).context("couldn't read file")?.len();
+            if len > STREAMING_THRESHOLD {
+                return Self::open_streaming(path.clone(), |read| {
+                    log::debug!("loaded {read}/{len} bytes of {}", path.display());
+                });
+            }
+
+            read_file(path)?
         } else {
-            Rope::new()
+            (Rope::new(), false)
         };
 
         let cursor_index = rope.byte_len();
+        let saved_hash = path.is_some().then(|| content_hash(&rope));
 
         Ok(Self {
             rope,
             path,
             cursor_index,
+            saved_hash,
+            read_only: hex_dump,
+            hex_dump,
+            ..Default::default()
+        })
+    }
+
+    /// Open a file by reading it in chunks, rather than all at once.
+    /// `progress` is called with the number of bytes read so far after each
+    /// chunk. Files that aren't text are shown as a read-only hex dump, as
+    /// with [`Document::new`].
+    pub fn open_streaming(path: PathBuf, progress: impl FnMut(u64)) -> Result<Self> {
+        let file = File::open(&path).context("couldn't open file")?;
+        let (rope, hex_dump) = match read_rope(file, progress) {
+            Ok(rope) => (rope, false),
+            Err(err) if err.is::<NotText>() => read_file(&path)?,
+            Err(err) => return Err(err),
+        };
+
+        let cursor_index = rope.byte_len();
+        let saved_hash = Some(content_hash(&rope));
+
+        Ok(Self {
+            rope,
+            path: Some(path),
+            cursor_index,
+            saved_hash,
+            read_only: hex_dump,
+            hex_dump,
             ..Default::default()
         })
     }
 
-    pub fn save_file(&self) {
-        let snapshot = self.rope.clone();
+    /// Create a document from text read from `reader`, such as piped input.
+    /// The document has no path, so it has to be given one to be saved.
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        let rope = read_rope(reader, |_| {})?;
+        let cursor_index = rope.byte_len();
 
-        if let Some(path) = self.path.clone() {
-            // TODO: report errors properly
-            std::thread::spawn(move || {
-                let mut file = BufWriter::new(File::create(path).expect("failed to open file"));
-                for chunk in snapshot.chunks() {
-                    file.write_all(chunk.as_bytes())
-                        .expect("failed to write to file");
-                }
+        Ok(Self {
+            rope,
+            cursor_index,
+            ..Default::default()
+        })
+    }
+
+    pub fn save_file(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            bail!("no file name");
+        };
+
+        if self.read_only || self.hex_dump {
+            bail!("file is read-only");
+        }
+
+        write_file(&self.rope, path, self.final_newline).context("couldn't write file")?;
+        self.modified = false;
+        self.saved_hash = Some(self.content_hash());
+
+        Ok(())
+    }
+
+    /// Re-read the document from its path, discarding any changes. The cursor
+    /// and marks keep their byte indices, moved back to fit in the new text.
+    pub fn reload(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            bail!("no file name");
+        };
+
+        let (rope, hex_dump) = read_file(path)?;
+        let old_len = self.rope.byte_len();
+        self.rope = rope;
+        self.read_only = hex_dump;
+        self.hex_dump = hex_dump;
+
+        self.cursor_index = floor_char_boundary(&self.rope, self.cursor_index);
+        self.virtual_columns = 0;
+        for mark in self.marks.values_mut() {
+            *mark = floor_char_boundary(&self.rope, *mark);
+        }
+
+        // They were for the old text.
+        self.diagnostics.clear();
+
+        self.selection_anchor = None;
+        self.target_column = None;
+
+        self.modified = false;
+        self.saved_hash = Some(self.content_hash());
+
+        if self.on_change.is_some() {
+            self.pending_edits.push(Edit {
+                range: 0..old_len,
+                text: self.rope.to_string(),
             });
         }
+        self.notify_edits();
+
+        Ok(())
+    }
+
+    /// Whether the document shouldn't be edited or saved. Files that aren't
+    /// valid UTF-8 or contain NUL bytes are opened read-only, as a hex dump.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Fails when making a hex dump writable.
+    pub fn set_read_only(&mut self, read_only: bool) -> Result<()> {
+        if !read_only && self.hex_dump {
+            bail!("can't make a hex dump writable");
+        }
+
+        self.read_only = read_only;
+        Ok(())
+    }
+
+    pub fn set_final_newline(&mut self, final_newline: FinalNewline) {
+        self.final_newline = final_newline;
+    }
+
+    /// Whether the content differs from when the document was opened or last
+    /// saved. Edits that have been reverted by hand don't count.
+    pub fn is_modified(&self) -> bool {
+        self.modified && self.saved_hash != Some(self.content_hash())
+    }
+
+    /// A hash of the document's text, which doesn't depend on how the rope
+    /// happens to be chunked.
+    pub fn content_hash(&self) -> u64 {
+        content_hash(&self.rope)
     }
 
     pub fn rope(&self) -> &Rope {
         &self.rope
     }
 
-    pub fn scroll_offset(&self) -> OffsetUsize {
-        self.scroll_offset
+    /// The text in pieces, in order, without copying it into one string.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.rope.chunks()
     }
 
-    /// The cursor offset, in cells.
-    pub fn cursor_offset(&self) -> OffsetUsize {
-        let line = self.rope.line_of_byte(self.cursor_index);
-        let line_start = self.rope.byte_of_line(line);
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
 
-        // Fine to sum up the widths of each chunk - the `width` implementation just
-        // sums the character widths, so it seems there's nothing contextual
-        // that is lost by splitting up a string.
-        let column: usize = self
-            .rope
-            .byte_slice(line_start..self.cursor_index)
-            .chunks()
-            .map(|s| s.width())
-            .sum();
+    pub fn scroll_offset(&self) -> OffsetUsize {
+        self.scroll_offset
+    }
 
-        OffsetUsize::new(column, line)
+    /// Set the scroll offset, clamped as by [`Document::clamp_scroll_offset`].
+    pub fn set_scroll_offset(&mut self, scroll_offset: OffsetUsize, view_size: OffsetUsize) {
+        self.scroll_offset = self.clamp_scroll_offset(scroll_offset, view_size);
     }
 
-    pub fn scroll_to_show_cursor(&mut self, size: OffsetUsize) {
-        let cursor_offset = self.cursor_offset();
+    /// Clamp a scroll offset so that a view of `view_size` doesn't go past the
+    /// last line, or further right than the widest line in view.
+    pub fn clamp_scroll_offset(
+        &self,
+        scroll_offset: OffsetUsize,
+        view_size: OffsetUsize,
+    ) -> OffsetUsize {
+        let y = scroll_offset.y.min(self.max_scroll_y(view_size.y));
 
-        if cursor_offset.x < self.scroll_offset.x {
-            self.scroll_offset.x = cursor_offset.x;
-        } else if cursor_offset.x >= self.scroll_offset.x + size.x {
-            self.scroll_offset.x = cursor_offset.x - size.x + 1;
-        }
+        // Leave room for the cursor after the end of the line.
+        let widest = self
+            .visible_lines(OffsetUsize::new(0, y), view_size)
+            .map(|(_, line)| slice_width(line, self.tab_width) + 1)
+            .max()
+            .unwrap_or(1);
+        let x = scroll_offset.x.min(widest.saturating_sub(view_size.x));
 
-        if cursor_offset.y < self.scroll_offset.y {
-            self.scroll_offset.y = cursor_offset.y;
-        } else if cursor_offset.y >= self.scroll_offset.y + size.y {
-            self.scroll_offset.y = cursor_offset.y - size.y + 1;
-        }
+        OffsetUsize::new(x, y)
     }
 
-    pub fn insert_str(&mut self, s: &str) {
-        self.rope.insert(self.cursor_index, s);
-        self.cursor_index += s.len();
-        self.target_column = None;
+    /// Put the cursor and scroll offset back where they were, such as in a
+    /// previous session. Both are clamped to the text, in case it's changed
+    /// since.
+    pub fn restore_position(&mut self, cursor_index: usize, scroll_offset: OffsetUsize) {
+        self.set_cursor_index(floor_char_boundary(&self.rope, cursor_index));
+        self.scroll_offset =
+            OffsetUsize::new(scroll_offset.x, scroll_offset.y.min(self.max_scroll_y(1)));
     }
 
-    pub fn insert_str_after(&mut self, s: &str) {
-        self.rope.insert(self.cursor_index, s);
-        self.target_column = None;
+    /// The furthest the view can scroll down while still being filled, or
+    /// showing the whole document if it's shorter than the view.
+    fn max_scroll_y(&self, view_height: usize) -> usize {
+        let last_line = self.rope.line_of_byte(self.rope.byte_len());
+        (last_line + 1).saturating_sub(view_height)
     }
 
-    pub fn insert_char(&mut self, ch: char) {
-        self.insert_str(ch.encode_utf8(&mut [0; 4]));
+    /// The cursor position, as a byte index.
+    pub fn cursor_index(&self) -> usize {
+        self.cursor_index
     }
 
-    pub fn insert_char_after(&mut self, ch: char) {
-        self.insert_str_after(ch.encode_utf8(&mut [0; 4]));
-    }
+    /// Move the cursor to a byte index, which is clamped to the document.
+    ///
+    /// # Panics
+    /// Panics if the index is not on a char boundary.
+    pub fn set_cursor_index(&mut self, index: usize) {
+        let index = index.min(self.rope.byte_len());
+        assert!(self.rope.is_char_boundary(index), "not on char boundary");
 
-    pub fn backspace(&mut self) {
-        if let Some(prev) = self.grapheme_before_cursor() {
-            let prev_len = prev.len();
-            self.rope
-                .delete((self.cursor_index - prev_len)..self.cursor_index);
-            self.cursor_index -= prev_len;
-        }
+        self.cursor_index = index;
         self.target_column = None;
+        self.virtual_columns = 0;
     }
 
-    pub fn delete(&mut self) {
-        if let Some(next) = self.grapheme_after_cursor() {
-            self.rope
-                .delete(self.cursor_index..(self.cursor_index + next.len()));
+    pub fn stats(&self) -> DocStats {
+        let mut stats = DocStats {
+            bytes: self.rope.byte_len(),
+            lines: self.rope.line_len(),
+            ..Default::default()
+        };
+
+        // Words may be split across chunks.
+        let mut in_word = false;
+
+        for chunk in self.chunks() {
+            for ch in chunk.chars() {
+                stats.chars += 1;
+
+                let is_word_char = !ch.is_whitespace();
+                if is_word_char && !in_word {
+                    stats.words += 1;
+                }
+                in_word = is_word_char;
+            }
         }
-        self.target_column = None;
+
+        stats
     }
 
-    pub fn move_left(&mut self) {
-        if let Some(prev) = self.grapheme_before_cursor() {
-            self.cursor_index -= prev.len();
-        }
-        self.target_column = None;
+    /// The lines within a view of `size` scrolled by `scroll_offset`, along
+    /// with their line indices.
+    pub fn visible_lines(
+        &self,
+        scroll_offset: OffsetUsize,
+        size: OffsetUsize,
+    ) -> impl Iterator<Item = (usize, RopeSlice<'_>)> {
+        self.rope
+            .lines()
+            .enumerate()
+            .skip(scroll_offset.y)
+            .take(size.y)
     }
 
-    pub fn move_right(&mut self) {
-        if let Some(next) = self.grapheme_after_cursor() {
-            self.cursor_index += next.len();
+    /// Check the document's invariants, panicking if any are broken.
+    pub fn assert_invariants(&self) {
+        assert!(
+            self.cursor_index <= self.rope.byte_len(),
+            "cursor index {} out of bounds",
+            self.cursor_index
+        );
+        assert!(
+            self.rope.is_char_boundary(self.cursor_index),
+            "cursor index {} not on char boundary",
+            self.cursor_index
+        );
+
+        if let Some(anchor) = self.selection_anchor {
+            assert!(
+                anchor <= self.rope.byte_len(),
+                "selection anchor {anchor} out of bounds"
+            );
         }
-        self.target_column = None;
     }
 
-    pub fn move_up(&mut self) {
-        self.move_vertical(-1);
+    /// The byte range of a line, including its line ending, or `None` if the
+    /// line is past the end of the document. When the document ends in a
+    /// newline, the empty line after it has an empty range at the end.
+    #[allow(dead_code)]
+    pub fn line_byte_range(&self, line: usize) -> Option<Range<usize>> {
+        (line <= self.last_line()).then(|| self.line_range(line))
     }
 
-    pub fn move_down(&mut self) {
-        self.move_vertical(1);
+    /// The cursor offset, in cells.
+    pub fn cursor_offset(&self) -> OffsetUsize {
+        let line = self.rope.line_of_byte(self.cursor_index);
+        let line_start = self.rope.byte_of_line(line);
+
+        let column = slice_width(
+            self.safe_byte_slice(line_start..self.cursor_index),
+            self.tab_width,
+        );
+
+        OffsetUsize::new(column + self.virtual_columns, line)
     }
 
-    pub fn move_home(&mut self) {
-        self.go_to_offset(OffsetUsize::new(0, self.cursor_offset().y));
-        self.target_column = None;
+    /// The cursor position as a line and a column in chars, rather than cells.
+    pub fn cursor_line_col(&self) -> (usize, usize) {
+        let line = self.rope.line_of_byte(self.cursor_index);
+        let line_start = self.rope.byte_of_line(line);
+
+        let column = self
+            .safe_byte_slice(line_start..self.cursor_index)
+            .chars()
+            .count();
+
+        (line, column + self.virtual_columns)
     }
 
-    pub fn move_end(&mut self) {
-        let (_, line) = self.current_line();
-        let line_width = line.chunks().map(|chunk| chunk.width()).sum();
-        self.go_to_offset(OffsetUsize::new(line_width, self.cursor_offset().y));
+    /// Move the cursor to a line and a column in chars. Both are clamped to the
+    /// document.
+    pub fn set_cursor_line_col(&mut self, line: usize, column: usize) {
+        let line = line.min(self.last_line());
+        let line_start = self.rope.byte_of_line(line);
+
+        let line_slice = if line == self.rope.line_len() {
+            self.safe_byte_slice(line_start..)
+        } else {
+            self.rope.line(line)
+        };
+
+        let offset: usize = line_slice.chars().take(column).map(char::len_utf8).sum();
+
+        self.cursor_index = line_start + offset;
         self.target_column = None;
+        self.virtual_columns = 0;
     }
 
-    pub fn move_vertical(&mut self, n: isize) {
-        let prev_cursor_index = self.cursor_index;
-
-        'main: {
-            let cursor_offset = self.cursor_offset();
+    /// Set a function to call after each edit, once the cursor has been
+    /// updated.
+    #[allow(dead_code, reason = "for observers such as a language client")]
+    pub fn set_on_change(&mut self, on_change: ChangeHook) {
+        self.on_change = Some(on_change);
+    }
 
-            let Some(new_offset_y) = cursor_offset.y.checked_add_signed(n) else {
-                self.cursor_index = 0;
-                self.target_column = Some(0);
-                break 'main;
-            };
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
 
-            if new_offset_y >= self.rope.line_len() {
-                self.cursor_index = self.rope.byte_len();
+    /// Replace the document's diagnostics. They're kept on the same text as
+    /// the document is edited.
+    #[allow(
+        dead_code,
+        reason = "for a diagnostics source, such as a language client"
+    )]
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+    }
 
-                let num_lines = self.rope.line_len();
-                self.target_column = Some(match num_lines {
-                    0 => 0,
-                    _ => self
-                        .rope
-                        .line(num_lines - 1)
-                        .chunks()
-                        .map(|chunk| chunk.width())
-                        .sum(),
-                });
+    pub fn virtual_edit(&self) -> bool {
+        self.virtual_edit
+    }
 
-                break 'main;
-            }
+    /// Allow the cursor to move past the end of lines.
+    pub fn set_virtual_edit(&mut self, virtual_edit: bool) {
+        self.virtual_edit = virtual_edit;
+        self.virtual_columns = 0;
+    }
 
-            let new_offset_x = match self.target_column {
-                Some(col) => col,
-                None => {
-                    let col = cursor_offset.x;
-                    self.target_column = Some(col);
-                    col
-                }
-            };
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
 
-            self.go_to_offset(OffsetUsize::new(new_offset_x, new_offset_y));
-        }
+    /// Set the width of a tab stop. The cursor stays on the same character,
+    /// though its column may change.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
 
-        if self.cursor_index == prev_cursor_index {
+        // The column to move to was measured with the old tab width.
+        if self.target_column != Some(usize::MAX) {
             self.target_column = None;
         }
     }
 
-    fn go_to_offset(&mut self, offset: OffsetUsize) {
-        if offset.y >= self.rope.line_len() {
-            self.cursor_index = self.rope.byte_len();
-            return;
+    pub fn indent_style(&self) -> IndentStyle {
+        self.indent_style
+    }
+
+    pub fn set_indent_style(&mut self, indent_style: IndentStyle) {
+        self.indent_style = match indent_style {
+            IndentStyle::Spaces(n) => IndentStyle::Spaces(n.max(1)),
+            IndentStyle::Tabs => IndentStyle::Tabs,
         };
+    }
 
-        let line = self.rope.line(offset.y);
-        let line_start = self.rope.byte_of_line(offset.y);
+    /// The width of a level of indentation, in cells.
+    fn indent_width(&self) -> usize {
+        match self.indent_style {
+            IndentStyle::Spaces(n) => n,
+            IndentStyle::Tabs => self.tab_width,
+        }
+    }
 
-        let byte_offset = line.graphemes().try_fold((0, 0), |(acc, off), grapheme| {
-            let end = acc + grapheme.width();
-            if offset.x >= end {
-                ControlFlow::Continue((end, off + grapheme.len()))
-            } else {
-                ControlFlow::Break(off)
+    /// Indentation `width` cells wide, in the document's indent style. With
+    /// tabs, any width left over that's less than a tab is made up in spaces.
+    fn indent_string(&self, width: usize) -> String {
+        match self.indent_style {
+            IndentStyle::Spaces(_) => " ".repeat(width),
+            IndentStyle::Tabs => {
+                let mut indent = "\t".repeat(width / self.tab_width);
+                indent.push_str(&" ".repeat(width % self.tab_width));
+                indent
             }
-        });
+        }
+    }
 
-        let byte_offset = match byte_offset {
-            ControlFlow::Break(off) => off,
-            ControlFlow::Continue((_, off)) => off,
+    /// Remember the cursor position under `name`.
+    pub fn set_mark(&mut self, name: char) {
+        self.marks.insert(name, self.cursor_index);
+    }
+
+    /// Move the cursor to a mark, returning whether the mark exists.
+    pub fn jump_to_mark(&mut self, name: char) -> bool {
+        let Some(&index) = self.marks.get(&name) else {
+            return false;
         };
 
-        self.cursor_index = line_start + byte_offset;
+        self.set_cursor_index(index);
+        true
     }
 
-    fn grapheme_before_cursor(&self) -> Option<Cow<str>> {
-        self.rope_before_cursor().graphemes().next_back()
+    /// The selected byte range, if there is a selection.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?;
+        Some(anchor.min(self.cursor_index)..anchor.max(self.cursor_index))
     }
 
-    fn grapheme_after_cursor(&self) -> Option<Cow<str>> {
-        self.rope_after_cursor().graphemes().next()
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
     }
 
-    fn rope_before_cursor(&self) -> RopeSlice {
-        self.rope.byte_slice(..self.cursor_index)
+    /// Select the word under the cursor, leaving the cursor at its end.
+    pub fn select_word(&mut self) {
+        if let Some((range, _)) = self.word_at_cursor() {
+            self.selection_anchor = Some(range.start);
+            self.cursor_index = range.end;
+            self.target_column = None;
+            self.virtual_columns = 0;
+        }
     }
 
-    fn rope_after_cursor(&self) -> RopeSlice {
-        self.rope.byte_slice(self.cursor_index..)
+    /// The byte range and text of the word under the cursor, where a word is
+    /// a run of alphanumeric characters and underscores.
+    ///
+    /// If the cursor is just past the end of a word, that word is returned.
+    pub fn word_at_cursor(&self) -> Option<(Range<usize>, String)> {
+        let is_word_char = |ch: &char| ch.is_alphanumeric() || *ch == '_';
+
+        let before: usize = self
+            .rope_before_cursor()
+            .chars()
+            .rev()
+            .take_while(is_word_char)
+            .map(char::len_utf8)
+            .sum();
+
+        let after: usize = self
+            .rope_after_cursor()
+            .chars()
+            .take_while(is_word_char)
+            .map(char::len_utf8)
+            .sum();
+
+        if before == 0 && after == 0 {
+            return None;
+        }
+
+        let range = (self.cursor_index - before)..(self.cursor_index + after);
+        let word = self.rope.byte_slice(range.clone()).to_string();
+
+        Some((range, word))
     }
 
-    fn current_line(&self) -> (usize, RopeSlice) {
-        let line_num = self.rope.line_of_byte(self.cursor_index);
+    /// The byte range of the first match of `query` at or after `start`,
+    /// wrapping around to the start of the document.
+    pub fn find(&self, query: &str, start: usize) -> Option<Range<usize>> {
+        if query.is_empty() {
+            return None;
+        }
 
-        let slice = if line_num == self.rope.line_len() {
-            self.rope.byte_slice(self.cursor_index..)
-        } else {
-            self.rope.line(line_num)
-        };
+        let start = start.min(self.rope.byte_len());
 
-        (line_num, slice)
+        let index = find_in_slice(self.rope.byte_slice(start..), query)
+            .map(|index| start + index)
+            .or_else(|| find_in_slice(self.rope.byte_slice(..), query))?;
+
+        Some(index..(index + query.len()))
     }
-}
 
-pub trait RopeExt {
-    fn has_trailing_newline(&self) -> bool;
-}
+    /// Move the cursor to the next match of `query` after the cursor, wrapping
+    /// around, and return its byte index.
+    pub fn find_next(&mut self, query: &str) -> Option<usize> {
+        let start = self.cursor_index
+            + self
+                .grapheme_after_cursor()
+                .map(|g| g.len())
+                .unwrap_or_default();
 
-impl RopeExt for Rope {
-    fn has_trailing_newline(&self) -> bool {
-        match self.chunks().last() {
-            Some(chunk) => chunk.ends_with('\n'),
-            None => true,
-        }
+        let index = self.find(query, start)?.start;
+        self.set_cursor_index(index);
+
+        Some(index)
+    }
+
+    /// Like [`Document::find_next`], but for a regular expression.
+    #[cfg(feature = "regex")]
+    pub fn find_next_regex(&mut self, pattern: &str) -> Result<Option<usize>, regex::Error> {
+        let regex = regex::Regex::new(pattern)?;
+
+        let text = self.rope.to_string();
+        let start = self.cursor_index
+            + self
+                .grapheme_after_cursor()
+                .map(|g| g.len())
+                .unwrap_or_default();
+
+        let Some(m) = regex.find_at(&text, start).or_else(|| regex.find(&text)) else {
+            return Ok(None);
+        };
+
+        self.set_cursor_index(m.start());
+
+        Ok(Some(m.start()))
+    }
+
+    pub fn scroll_to_show_cursor(&mut self, size: OffsetUsize, scrolloff: usize) {
+        let cursor_offset = self.cursor_offset();
+
+        // Keep `scrolloff` cells of context around the cursor, unless the view
+        // is too small for it.
+        let margin = OffsetUsize::new(
+            scrolloff.min(size.x.saturating_sub(1) / 2),
+            scrolloff.min(size.y.saturating_sub(1) / 2),
+        );
+
+        if cursor_offset.x < self.scroll_offset.x + margin.x {
+            self.scroll_offset.x = cursor_offset.x.saturating_sub(margin.x);
+        } else if cursor_offset.x + margin.x >= self.scroll_offset.x + size.x {
+            self.scroll_offset.x = (cursor_offset.x + margin.x + 1).saturating_sub(size.x);
+        }
+
+        let max_scroll = self.max_scroll_y(size.y);
+
+        if cursor_offset.y < self.scroll_offset.y + margin.y {
+            self.scroll_offset.y = cursor_offset.y.saturating_sub(margin.y);
+        } else if cursor_offset.y + margin.y >= self.scroll_offset.y + size.y {
+            self.scroll_offset.y = (cursor_offset.y + margin.y + 1)
+                .saturating_sub(size.y)
+                .min(max_scroll);
+        }
+    }
+
+    /// Like [`Document::scroll_to_show_cursor`], but for when lines are
+    /// wrapped to the width of the view.
+    pub fn scroll_to_show_cursor_wrapped(&mut self, size: OffsetUsize) {
+        self.scroll_offset.x = 0;
+
+        let cursor_line = self.cursor_offset().y;
+
+        if cursor_line < self.scroll_offset.y {
+            self.scroll_offset.y = cursor_line;
+            return;
+        }
+
+        // Each line takes up at least one row.
+        if cursor_line - self.scroll_offset.y >= size.y {
+            self.scroll_offset.y = cursor_line + 1 - size.y.max(1);
+        }
+
+        let mut row: usize = (self.scroll_offset.y..cursor_line)
+            .map(|line| self.line_rows(line, size.x))
+            .sum::<usize>()
+            + self.cursor_offset_wrapped(size.x).y;
+
+        while row >= size.y && self.scroll_offset.y < cursor_line {
+            row -= self.line_rows(self.scroll_offset.y, size.x);
+            self.scroll_offset.y += 1;
+        }
+    }
+
+    /// The cursor offset relative to the start of its line, in cells, when
+    /// lines are wrapped at `width` cells.
+    pub fn cursor_offset_wrapped(&self, width: usize) -> OffsetUsize {
+        let line_start = self.rope.byte_of_line(self.cursor_offset().y);
+        let (_, line) = self.current_line();
+        let index = self.cursor_index - line_start;
+
+        let mut end = OffsetUsize::ZERO;
+
+        for grapheme in wrap_graphemes(line, width, self.tab_width) {
+            if grapheme.byte >= index {
+                return grapheme.pos;
+            }
+
+            end = OffsetUsize::new(grapheme.pos.x + grapheme.width, grapheme.pos.y);
+        }
+
+        // Keep the cursor on the last row of the line at the end of a full row.
+        OffsetUsize::new(end.x.min(width.saturating_sub(1)), end.y)
+    }
+
+    /// The number of rows a line takes up when wrapped at `width` cells.
+    fn line_rows(&self, line: usize, width: usize) -> usize {
+        if line >= self.rope.line_len() {
+            return 1;
+        }
+
+        wrap_graphemes(self.rope.line(line), width, self.tab_width)
+            .last()
+            .map_or(1, |grapheme| grapheme.pos.y + 1)
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.fill_virtual_columns();
+        self.rope_insert(self.cursor_index, s);
+        self.cursor_index += s.len();
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    pub fn insert_str_after(&mut self, s: &str) {
+        self.fill_virtual_columns();
+        self.rope_insert(self.cursor_index, s);
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    pub fn insert_char(&mut self, ch: char) {
+        self.insert_str(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn insert_char_after(&mut self, ch: char) {
+        self.insert_str_after(ch.encode_utf8(&mut [0; 4]));
+    }
+
+    /// Insert a tab, or with spaces for indentation, spaces up to the next
+    /// indentation level.
+    pub fn insert_tab(&mut self) {
+        match self.indent_style {
+            IndentStyle::Spaces(n) => {
+                let column = self.cursor_offset().x;
+                self.insert_str(&" ".repeat(n - column % n));
+            }
+            IndentStyle::Tabs => self.insert_char('\t'),
+        }
+    }
+
+    /// Insert a newline, followed by the current line's indentation up to the
+    /// cursor, redone in the document's indent style.
+    pub fn insert_newline_with_indent(&mut self) {
+        let (line_num, _) = self.current_line();
+        let line_start = self.rope.byte_of_line(line_num);
+        let before_cursor = self
+            .safe_byte_slice(line_start..self.cursor_index)
+            .to_string();
+
+        let indent = &before_cursor
+            [..before_cursor.len() - before_cursor.trim_start_matches([' ', '\t']).len()];
+        let width = text_width(indent, self.tab_width);
+
+        let mut text = "\n".to_owned();
+        text.push_str(&self.indent_string(width));
+        self.insert_str(&text);
+    }
+
+    /// Add a level of indentation to the start of the current line, or each
+    /// line the selection covers. Empty lines are left alone.
+    pub fn indent(&mut self) {
+        let indent = self.indent_string(self.indent_width());
+
+        for line_num in self.selected_lines().rev() {
+            if self.rope.line_text(line_num).is_empty() {
+                continue;
+            }
+
+            let line_start = self.rope.byte_of_line(line_num);
+            self.rope_insert(line_start, &indent);
+
+            for pos in [&mut self.cursor_index]
+                .into_iter()
+                .chain(self.selection_anchor.as_mut())
+            {
+                if *pos >= line_start {
+                    *pos += indent.len();
+                }
+            }
+        }
+
+        self.target_column = None;
+        self.notify_edits();
+    }
+
+    /// Remove a level of indentation from the start of the current line, or
+    /// each line the selection covers: a tab, or up to a level's worth of
+    /// spaces. Returns whether there was any indentation to remove.
+    pub fn dedent(&mut self) -> bool {
+        let mut dedented = false;
+
+        for line_num in self.selected_lines().rev() {
+            let line_start = self.rope.byte_of_line(line_num);
+            let line = self.rope.line_text(line_num);
+
+            let len = match line.starts_with('\t') {
+                true => 1,
+                false => line
+                    .bytes()
+                    .take(self.indent_width())
+                    .take_while(|&byte| byte == b' ')
+                    .count(),
+            };
+            if len == 0 {
+                continue;
+            }
+
+            self.rope_delete(line_start..(line_start + len));
+            dedented = true;
+
+            for pos in [&mut self.cursor_index]
+                .into_iter()
+                .chain(self.selection_anchor.as_mut())
+            {
+                if *pos > line_start {
+                    *pos = pos.saturating_sub(len).max(line_start);
+                }
+            }
+        }
+
+        self.target_column = None;
+        self.notify_edits();
+
+        dedented
+    }
+
+    /// The lines covered by the selection, or the current line if there
+    /// isn't one. A selection ending at the start of a line doesn't cover it.
+    fn selected_lines(&self) -> RangeInclusive<usize> {
+        let Some(selection) = self.selection() else {
+            let (line_num, _) = self.current_line();
+            return line_num..=line_num;
+        };
+
+        let start = self.rope.line_of_byte(selection.start);
+        let mut end = self.rope.line_of_byte(selection.end);
+
+        if end > start && self.rope.byte_of_line(end) == selection.end {
+            end -= 1;
+        }
+
+        start..=end
+    }
+
+    /// Insert a character, pairing brackets and quotes.
+    ///
+    /// Typing an opening bracket or quote also inserts its closer after the
+    /// cursor, or wraps the selection if there is one. Typing a closer when
+    /// the same closer is just after the cursor moves over it instead.
+    pub fn insert_char_paired(&mut self, ch: char) {
+        let next = self.rope_after_cursor().chars().next();
+        let is_closer = AUTO_PAIRS.iter().any(|&(_, close)| close == ch);
+
+        if is_closer && next == Some(ch) && self.selection_anchor.is_none() {
+            self.move_right();
+            return;
+        }
+
+        let Some(&(open, close)) = AUTO_PAIRS.iter().find(|&&(open, _)| open == ch) else {
+            self.insert_char(ch);
+            return;
+        };
+
+        if let (Some(anchor), Some(selection)) = (self.selection_anchor, self.selection()) {
+            self.rope_insert(selection.end, close.encode_utf8(&mut [0; 4]));
+            self.rope_insert(selection.start, open.encode_utf8(&mut [0; 4]));
+
+            self.selection_anchor = Some(anchor + open.len_utf8());
+            self.cursor_index += open.len_utf8();
+            self.target_column = None;
+            self.notify_edits();
+            return;
+        }
+
+        // Don't pair quotes typed straight after a word, as they're probably
+        // apostrophes or closing quotes.
+        let prev = self.rope_before_cursor().chars().next_back();
+        if open == close && prev.is_some_and(|prev| prev.is_alphanumeric() || prev == '_') {
+            self.insert_char(ch);
+            return;
+        }
+
+        self.insert_char(open);
+        self.insert_char_after(close);
+    }
+
+    /// Overwrite the grapheme under the cursor with a character, or insert it
+    /// if the cursor is at the end of a line.
+    pub fn replace_char(&mut self, ch: char) {
+        if let Some(next) = self.grapheme_after_cursor() {
+            if next != "\n" && next != "\r\n" {
+                self.delete();
+            }
+        }
+
+        self.insert_char(ch);
+    }
+
+    pub fn backspace(&mut self) {
+        // Past the end of the line, there's only virtual space to delete.
+        if self.virtual_columns > 0 {
+            self.virtual_columns -= 1;
+            return;
+        }
+
+        if let Some(prev) = self.grapheme_before_cursor() {
+            let prev_len = prev.len();
+            self.rope_delete((self.cursor_index - prev_len)..self.cursor_index);
+            self.cursor_index -= prev_len;
+        }
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    /// Delete the brackets or quotes either side of the cursor, like `(|)`,
+    /// as if backspacing over an automatically closed pair. Returns whether
+    /// there was a pair to delete.
+    pub fn delete_pair(&mut self) -> bool {
+        if self.virtual_columns > 0 {
+            return false;
+        }
+
+        let prev = self.rope_before_cursor().chars().next_back();
+        let next = self.rope_after_cursor().chars().next();
+        let (Some(prev), Some(next)) = (prev, next) else {
+            return false;
+        };
+
+        if !AUTO_PAIRS.contains(&(prev, next)) {
+            return false;
+        }
+
+        let start = self.cursor_index - prev.len_utf8();
+        self.rope_delete(start..(self.cursor_index + next.len_utf8()));
+        self.cursor_index = start;
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+
+        true
+    }
+
+    /// Like [`backspace`](Self::backspace), but if the cursor is in
+    /// indentation made of spaces, delete back to the previous indentation
+    /// level.
+    pub fn backspace_indent_aware(&mut self) {
+        if self.virtual_columns > 0 {
+            self.backspace();
+            return;
+        }
+
+        let line_start = self
+            .rope
+            .byte_of_line(self.rope.line_of_byte(self.cursor_index));
+        let indent = self.safe_byte_slice(line_start..self.cursor_index);
+
+        let column = indent.byte_len();
+        if column == 0 || indent.bytes().any(|byte| byte != b' ') {
+            self.backspace();
+            return;
+        }
+
+        let len = match column % self.indent_width() {
+            0 => self.indent_width(),
+            n => n,
+        };
+
+        self.rope_delete((self.cursor_index - len)..self.cursor_index);
+        self.cursor_index -= len;
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    pub fn delete(&mut self) {
+        // Past the end of the line, there's nothing under the cursor.
+        if self.virtual_columns > 0 {
+            return;
+        }
+
+        if let Some(next) = self.grapheme_after_cursor() {
+            self.rope_delete(self.cursor_index..(self.cursor_index + next.len()));
+        }
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    pub fn move_by(&mut self, motion: Motion) {
+        if !matches!(
+            motion,
+            Motion::CharLeft | Motion::CharRight | Motion::LineUp | Motion::LineDown
+        ) {
+            self.virtual_columns = 0;
+        }
+
+        match motion {
+            Motion::CharLeft => self.move_left(),
+            Motion::CharRight => self.move_right(),
+            Motion::LineUp => self.move_up(),
+            Motion::LineDown => self.move_down(),
+
+            Motion::WordLeft => {
+                let mut graphemes = self.rope_before_cursor().graphemes().rev().peekable();
+                let mut len = 0;
+
+                while let Some(g) = graphemes.next_if(|g| CharClass::of(g) == CharClass::Whitespace)
+                {
+                    len += g.len();
+                }
+                if let Some(class) = graphemes.peek().map(|g| CharClass::of(g)) {
+                    while let Some(g) = graphemes.next_if(|g| CharClass::of(g) == class) {
+                        len += g.len();
+                    }
+                }
+
+                self.cursor_index -= len;
+                self.target_column = None;
+            }
+            Motion::WordRight => {
+                let mut graphemes = self.rope_after_cursor().graphemes().peekable();
+                let mut len = 0;
+
+                if let Some(class) = graphemes.peek().map(|g| CharClass::of(g)) {
+                    if class != CharClass::Whitespace {
+                        while let Some(g) = graphemes.next_if(|g| CharClass::of(g) == class) {
+                            len += g.len();
+                        }
+                    }
+                }
+                while let Some(g) = graphemes.next_if(|g| CharClass::of(g) == CharClass::Whitespace)
+                {
+                    len += g.len();
+                }
+
+                self.cursor_index += len;
+                self.target_column = None;
+            }
+
+            Motion::LineStart => self.move_home(),
+            Motion::SmartLineStart => self.move_home_smart(),
+            Motion::LineEnd => self.move_end(),
+
+            Motion::DocumentStart => self.set_cursor_index(0),
+            Motion::DocumentEnd => self.set_cursor_index(self.rope.byte_len()),
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        if self.virtual_columns > 0 {
+            self.virtual_columns -= 1;
+        } else {
+            self.cursor_index = self.prev_grapheme_boundary(self.cursor_index);
+        }
+        self.target_column = None;
+    }
+
+    pub fn move_right(&mut self) {
+        let at_line_end = self
+            .grapheme_after_cursor()
+            .is_none_or(|next| next == "\n" || next == "\r\n");
+
+        if self.virtual_edit && at_line_end {
+            self.virtual_columns += 1;
+        } else {
+            self.cursor_index = self.next_grapheme_boundary(self.cursor_index);
+        }
+        self.target_column = None;
+    }
+
+    pub fn move_up(&mut self) {
+        self.move_vertical(-1);
+    }
+
+    pub fn move_down(&mut self) {
+        self.move_vertical(1);
+    }
+
+    pub fn move_home(&mut self) {
+        self.go_to_offset(OffsetUsize::new(0, self.cursor_offset().y));
+        self.target_column = None;
+    }
+
+    /// Move to the first non-blank character of the line, or to the start of
+    /// the line if the cursor's already there or the line is blank.
+    pub fn move_home_smart(&mut self) {
+        let (line_num, _) = self.current_line();
+        let line_start = self.rope.byte_of_line(line_num);
+        let line = self.rope.line_text(line_num);
+
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        let first_non_blank = line_start + indent;
+
+        let at_first_non_blank = self.cursor_index == first_non_blank && self.virtual_columns == 0;
+        if indent == line.len() || at_first_non_blank {
+            self.set_cursor_index(line_start);
+        } else {
+            self.set_cursor_index(first_non_blank);
+        }
+    }
+
+    pub fn move_end(&mut self) {
+        let (line_num, _) = self.current_line();
+        let line_width = text_width(&self.rope.line_text(line_num), self.tab_width);
+        self.go_to_offset(OffsetUsize::new(line_width, self.cursor_offset().y));
+
+        // Stay at the end of lines when moving up or down.
+        self.target_column = Some(usize::MAX);
+    }
+
+    /// Insert a copy of the current line below it, moving the cursor onto the
+    /// copy.
+    pub fn duplicate_line(&mut self) {
+        let (line_num, _) = self.current_line();
+        if line_num == self.rope.line_len() {
+            return;
+        }
+
+        let range = self.line_range(line_num);
+        let mut text = self.rope.byte_slice(range.clone()).to_string();
+
+        if !text.ends_with('\n') {
+            text.insert(0, '\n');
+        }
+
+        self.rope_insert(range.end, &text);
+        self.cursor_index += text.len();
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    /// Delete the current line, including its newline. On the last line, the
+    /// preceding newline is deleted instead.
+    pub fn delete_line(&mut self) {
+        let (line_num, _) = self.current_line();
+        if line_num == self.rope.line_len() {
+            return;
+        }
+
+        let mut range = self.line_range(line_num);
+
+        if range.end == self.rope.byte_len() && !self.rope.has_trailing_newline() {
+            if let Some(newline) = self.safe_byte_slice(..range.start).graphemes().next_back() {
+                range.start -= newline.len();
+            }
+        }
+
+        self.rope_delete(range.clone());
+
+        let line_num = self.rope.line_of_byte(range.start);
+        self.cursor_index = self.rope.byte_of_line(line_num);
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    /// Join the current line with the next, replacing the newline and the next
+    /// line's indentation with a single space.
+    pub fn join_lines(&mut self) {
+        let (line_num, _) = self.current_line();
+        if line_num + 1 >= self.rope.line_len() {
+            return;
+        }
+
+        let line_end = self.line_range(line_num).end;
+        let Some(newline) = self.safe_byte_slice(..line_end).graphemes().next_back() else {
+            return;
+        };
+        let join_index = line_end - newline.len();
+
+        let next_line = self.rope.line(line_num + 1);
+        let indent: usize = next_line
+            .chars()
+            .take_while(|ch| matches!(ch, ' ' | '\t'))
+            .map(char::len_utf8)
+            .sum();
+        let next_line_empty = indent == next_line.byte_len();
+
+        self.rope_delete(join_index..(line_end + indent));
+
+        if !next_line_empty {
+            self.rope_insert(join_index, " ");
+        }
+
+        self.cursor_index = join_index;
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+    }
+
+    /// Add `delta` to the number under the cursor, or the next one after it
+    /// on the same line, leaving the cursor on its last digit. Numbers with
+    /// leading zeros keep their width. Returns whether there was a number.
+    pub fn modify_number(&mut self, delta: i64) -> bool {
+        let (line_num, _) = self.current_line();
+        if line_num == self.rope.line_len() {
+            return false;
+        }
+
+        let line_start = self.rope.byte_of_line(line_num);
+
+        let (range, text) = {
+            let line = self.rope.line_text(line_num);
+            let bytes = line.as_bytes();
+            let column = (self.cursor_index - line_start).min(bytes.len());
+
+            let digits_start = if bytes.get(column).is_some_and(u8::is_ascii_digit) {
+                bytes[..column]
+                    .iter()
+                    .rposition(|byte| !byte.is_ascii_digit())
+                    .map_or(0, |i| i + 1)
+            } else {
+                match bytes[column..].iter().position(u8::is_ascii_digit) {
+                    Some(i) => column + i,
+                    None => return false,
+                }
+            };
+
+            let digits_end = bytes[digits_start..]
+                .iter()
+                .position(|byte| !byte.is_ascii_digit())
+                .map_or(bytes.len(), |i| digits_start + i);
+
+            let digits = &line[digits_start..digits_end];
+            let Ok(magnitude) = digits.parse::<i64>() else {
+                return false;
+            };
+
+            let negative = digits_start > 0 && bytes[digits_start - 1] == b'-';
+            let value = match negative {
+                true => -magnitude,
+                false => magnitude,
+            };
+            let new_value = value.saturating_add(delta);
+
+            let width = match digits.len() > 1 && digits.starts_with('0') {
+                true => digits.len(),
+                false => 0,
+            };
+            let mut text = format!("{:0width$}", new_value.unsigned_abs());
+            if new_value < 0 {
+                text.insert(0, '-');
+            }
+
+            let start = digits_start - usize::from(negative);
+            ((line_start + start)..(line_start + digits_end), text)
+        };
+
+        self.rope_delete(range.clone());
+        self.rope_insert(range.start, &text);
+
+        self.cursor_index = range.start + text.len() - 1;
+        self.target_column = None;
+        self.selection_anchor = None;
+        self.notify_edits();
+
+        true
+    }
+
+    /// Move the cursor by `pages` pages, scrolling the view by the same amount.
+    pub fn move_page(&mut self, pages: isize, page_height: usize) {
+        let n = pages.saturating_mul(page_height as isize);
+
+        self.move_vertical(n);
+
+        self.scroll_offset.y = self
+            .scroll_offset
+            .y
+            .saturating_add_signed(n)
+            .min(self.cursor_offset().y);
+    }
+
+    /// Scroll so that the cursor line is in the middle of the view.
+    pub fn center_cursor(&mut self, view_height: usize) {
+        self.scroll_offset.y = self.cursor_offset().y.saturating_sub(view_height / 2);
+    }
+
+    pub fn move_vertical(&mut self, n: isize) {
+        let prev_cursor_index = self.cursor_index;
+
+        'main: {
+            let cursor_offset = self.cursor_offset();
+
+            let Some(new_offset_y) = cursor_offset.y.checked_add_signed(n) else {
+                self.cursor_index = 0;
+                self.virtual_columns = 0;
+                self.target_column = Some(0);
+                break 'main;
+            };
+
+            let last_line = self.last_line();
+            if new_offset_y > last_line {
+                self.cursor_index = self.rope.byte_len();
+                self.virtual_columns = 0;
+                self.target_column =
+                    Some(text_width(&self.rope.line_text(last_line), self.tab_width));
+
+                break 'main;
+            }
+
+            let new_offset_x = match self.target_column {
+                Some(col) => col,
+                None => {
+                    let col = cursor_offset.x;
+                    self.target_column = Some(col);
+                    col
+                }
+            };
+
+            self.go_to_offset(OffsetUsize::new(new_offset_x, new_offset_y));
+        }
+
+        if self.cursor_index == prev_cursor_index {
+            self.target_column = None;
+        }
+    }
+
+    fn go_to_offset(&mut self, offset: OffsetUsize) {
+        self.virtual_columns = 0;
+
+        if offset.y > self.last_line() {
+            self.cursor_index = self.rope.byte_len();
+            return;
+        };
+
+        let line_start = self.rope.byte_of_line(offset.y);
+        let line = if offset.y == self.rope.line_len() {
+            // The empty line after a trailing newline.
+            self.safe_byte_slice(line_start..)
+        } else {
+            self.rope.line(offset.y)
+        };
+
+        let byte_offset = line.graphemes().try_fold((0, 0), |(acc, off), grapheme| {
+            let end = acc + grapheme_width(&grapheme, acc, self.tab_width);
+            if offset.x >= end {
+                ControlFlow::Continue((end, off + grapheme.len()))
+            } else {
+                ControlFlow::Break(off)
+            }
+        });
+
+        let byte_offset = match byte_offset {
+            ControlFlow::Break(off) => off,
+            ControlFlow::Continue((width, off)) => {
+                // Past the end of the line. A target of `usize::MAX` means the
+                // end of the line itself.
+                if self.virtual_edit && offset.x != usize::MAX {
+                    self.virtual_columns = offset.x - width;
+                }
+                off
+            }
+        };
+
+        self.cursor_index = line_start + byte_offset;
+    }
+
+    /// The grapheme starting at a byte index, or `None` at the end of the
+    /// document.
+    ///
+    /// `index` should be on a grapheme boundary. If it's inside a grapheme,
+    /// the rest of that grapheme is returned.
+    pub fn grapheme_at(&self, index: usize) -> Option<Cow<'_, str>> {
+        self.safe_byte_slice(index..).graphemes().next()
+    }
+
+    /// The byte index of the grapheme boundary after `index`, or the end of
+    /// the document.
+    pub fn next_grapheme_boundary(&self, index: usize) -> usize {
+        match self.grapheme_at(index) {
+            Some(grapheme) => index + grapheme.len(),
+            None => index,
+        }
+    }
+
+    /// The byte index of the grapheme boundary before `index`, or zero.
+    pub fn prev_grapheme_boundary(&self, index: usize) -> usize {
+        match self.safe_byte_slice(..index).graphemes().next_back() {
+            Some(grapheme) => index - grapheme.len(),
+            None => 0,
+        }
+    }
+
+    /// Replace every occurrence of `from` with `to`, returning the number of
+    /// replacements. The cursor and marks stay on the same text, or move to
+    /// the start of a replacement they were inside.
+    pub fn replace_all(&mut self, from: &str, to: &str) -> usize {
+        if from.is_empty() {
+            return 0;
+        }
+
+        let text = self.rope.to_string();
+        let matches: Vec<usize> = text.match_indices(from).map(|(i, _)| i).collect();
+
+        // Replace from the end, so the earlier matches' indices stay valid.
+        for &start in matches.iter().rev() {
+            let range = start..start + from.len();
+
+            if self.cursor_index >= range.end {
+                self.cursor_index = self.cursor_index - from.len() + to.len();
+            } else if self.cursor_index > range.start {
+                self.cursor_index = range.start;
+            }
+
+            self.rope_replace(range, to);
+        }
+
+        if !matches.is_empty() {
+            self.target_column = None;
+            self.selection_anchor = None;
+            self.notify_edits();
+        }
+
+        matches.len()
+    }
+
+    /// Insert text into the rope, shifting any marks and diagnostics after
+    /// it.
+    fn rope_insert(&mut self, index: usize, text: &str) {
+        self.rope.insert(index, text);
+        self.modified = true;
+        self.virtual_columns = 0;
+
+        if self.on_change.is_some() {
+            self.pending_edits.push(Edit {
+                range: index..index,
+                text: text.to_owned(),
+            });
+        }
+
+        let positions = self.marks.values_mut().chain(
+            self.diagnostics
+                .iter_mut()
+                .flat_map(|diagnostic| [&mut diagnostic.range.start, &mut diagnostic.range.end]),
+        );
+
+        for pos in positions {
+            if *pos > index {
+                *pos += text.len();
+            }
+        }
+    }
+
+    /// Delete a range of the rope, shifting any marks and diagnostics after
+    /// it. Those inside the range move to its start.
+    fn rope_delete(&mut self, range: Range<usize>) {
+        self.rope.delete(range.clone());
+        self.modified = true;
+        self.virtual_columns = 0;
+
+        if self.on_change.is_some() {
+            self.pending_edits.push(Edit {
+                range: range.clone(),
+                text: String::new(),
+            });
+        }
+
+        let positions = self.marks.values_mut().chain(
+            self.diagnostics
+                .iter_mut()
+                .flat_map(|diagnostic| [&mut diagnostic.range.start, &mut diagnostic.range.end]),
+        );
+
+        for pos in positions {
+            if *pos >= range.end {
+                *pos -= range.len();
+            } else if *pos > range.start {
+                *pos = range.start;
+            }
+        }
+    }
+
+    /// Replace a non-empty range of the rope with `text`, shifting any marks
+    /// and diagnostics after it. Those inside the range move to its start,
+    /// and those at its end stay at the end of the new text.
+    fn rope_replace(&mut self, range: Range<usize>, text: &str) {
+        self.rope.replace(range.clone(), text);
+        self.modified = true;
+        self.virtual_columns = 0;
+
+        if self.on_change.is_some() {
+            self.pending_edits.push(Edit {
+                range: range.clone(),
+                text: text.to_owned(),
+            });
+        }
+
+        let positions = self.marks.values_mut().chain(
+            self.diagnostics
+                .iter_mut()
+                .flat_map(|diagnostic| [&mut diagnostic.range.start, &mut diagnostic.range.end]),
+        );
+
+        for pos in positions {
+            if *pos >= range.end {
+                *pos = *pos - range.len() + text.len();
+            } else if *pos > range.start {
+                *pos = range.start;
+            }
+        }
+    }
+
+    /// Insert spaces up to the cursor, if it's past the end of the line.
+    fn fill_virtual_columns(&mut self) {
+        if self.virtual_columns > 0 {
+            let padding = " ".repeat(self.virtual_columns);
+            self.rope_insert(self.cursor_index, &padding);
+            self.cursor_index += padding.len();
+        }
+    }
+
+    /// Pass the edits made so far to `on_change`. Called at the end of each
+    /// editing method, once the cursor is up to date.
+    fn notify_edits(&mut self) {
+        if let Some(on_change) = &mut self.on_change {
+            for edit in self.pending_edits.drain(..) {
+                on_change(&edit);
+            }
+        }
+    }
+
+    fn grapheme_before_cursor(&self) -> Option<Cow<'_, str>> {
+        self.rope_before_cursor().graphemes().next_back()
+    }
+
+    fn grapheme_after_cursor(&self) -> Option<Cow<'_, str>> {
+        self.grapheme_at(self.cursor_index)
+    }
+
+    fn rope_before_cursor(&self) -> RopeSlice<'_> {
+        self.safe_byte_slice(..self.cursor_index)
+    }
+
+    fn rope_after_cursor(&self) -> RopeSlice<'_> {
+        self.safe_byte_slice(self.cursor_index..)
+    }
+
+    /// Like [`Rope::byte_slice`], but in debug builds checks that the range is
+    /// in bounds and on char boundaries, so that a misplaced index is reported
+    /// clearly.
+    fn safe_byte_slice(&self, range: impl RangeBounds<usize>) -> RopeSlice<'_> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.rope.byte_len(),
+        };
+
+        debug_assert!(
+            start <= end && end <= self.rope.byte_len(),
+            "byte range {start}..{end} out of bounds"
+        );
+        debug_assert!(
+            self.rope.is_char_boundary(start) && self.rope.is_char_boundary(end),
+            "byte range {start}..{end} not on char boundaries"
+        );
+
+        self.rope.byte_slice(start..end)
+    }
+
+    /// The byte range of a line, including its newline.
+    fn line_range(&self, line_num: usize) -> Range<usize> {
+        let start = self.rope.byte_of_line(line_num);
+        let end = if line_num + 1 < self.rope.line_len() {
+            self.rope.byte_of_line(line_num + 1)
+        } else {
+            self.rope.byte_len()
+        };
+
+        start..end
+    }
+
+    /// The last line the cursor can be on. When the document ends in a
+    /// newline, this is the empty line after it.
+    fn last_line(&self) -> usize {
+        self.rope.line_of_byte(self.rope.byte_len())
+    }
+
+    fn current_line(&self) -> (usize, RopeSlice<'_>) {
+        let line_num = self.rope.line_of_byte(self.cursor_index);
+
+        let slice = if line_num == self.rope.line_len() {
+            self.safe_byte_slice(self.cursor_index..)
+        } else {
+            self.rope.line(line_num)
+        };
+
+        (line_num, slice)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Whitespace,
+    Word,
+    Punctuation,
+}
+
+impl CharClass {
+    /// The class of a grapheme, going by its first char.
+    fn of(grapheme: &str) -> Self {
+        let ch = grapheme.chars().next().unwrap_or(' ');
+
+        if ch.is_whitespace() {
+            Self::Whitespace
+        } else if ch.is_alphanumeric() || ch == '_' {
+            Self::Word
+        } else {
+            Self::Punctuation
+        }
+    }
+}
+
+/// Brackets and quotes that are closed automatically when typed.
+const AUTO_PAIRS: [(char, char); 6] = [
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
+/// Files larger than this, in bytes, are opened with
+/// [`Document::open_streaming`].
+#[cfg(not(test))]
+const STREAMING_THRESHOLD: u64 = 8 * 1024 * 1024;
+#[cfg(test)]
+const STREAMING_THRESHOLD: u64 = 1024;
+
+/// Width of a tab stop, in cells, unless set otherwise.
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+
+/// The width of a grapheme in cells, when it starts at column `x`.
+pub fn grapheme_width(grapheme: &str, x: usize, tab_width: usize) -> usize {
+    match grapheme {
+        "\t" => tab_width - x % tab_width,
+        _ => grapheme.width(),
+    }
+}
+
+/// The width of a slice starting at column zero, in cells.
+pub fn slice_width(slice: RopeSlice<'_>, tab_width: usize) -> usize {
+    slice
+        .graphemes()
+        .fold(0, |x, grapheme| x + grapheme_width(&grapheme, x, tab_width))
+}
+
+/// The width of some text in cells, starting at the beginning of a line.
+pub fn text_width(text: &str, tab_width: usize) -> usize {
+    text.graphemes(true)
+        .fold(0, |x, grapheme| x + grapheme_width(grapheme, x, tab_width))
+}
+
+pub struct WrappedGrapheme<'a> {
+    pub grapheme: Cow<'a, str>,
+
+    /// Byte offset from the start of the line.
+    pub byte: usize,
+
+    /// Position relative to the start of the line, in cells.
+    pub pos: OffsetUsize,
+
+    /// Width, in cells.
+    pub width: usize,
+}
+
+/// Lay out the graphemes of a line in rows of `width` cells.
+pub fn wrap_graphemes(
+    line: RopeSlice<'_>,
+    width: usize,
+    tab_width: usize,
+) -> impl Iterator<Item = WrappedGrapheme<'_>> {
+    let mut pos = OffsetUsize::ZERO;
+    let mut byte = 0;
+    let mut column = 0;
+
+    line.graphemes().map(move |grapheme| {
+        let grapheme_width = grapheme_width(&grapheme, column, tab_width);
+
+        if pos.x > 0 && pos.x + grapheme_width > width {
+            pos = OffsetUsize::new(0, pos.y + 1);
+        }
+
+        let wrapped = WrappedGrapheme {
+            byte,
+            pos,
+            width: grapheme_width,
+            grapheme,
+        };
+
+        byte += wrapped.grapheme.len();
+        column += grapheme_width;
+        pos.x += grapheme_width;
+
+        wrapped
+    })
+}
+
+/// Read a file into a rope, returning whether it's a hex dump. Files that
+/// aren't valid UTF-8, or contain NUL bytes, are read as one.
+fn read_file(path: &Path) -> Result<(Rope, bool)> {
+    let bytes = fs::read(path).context("couldn't read file")?;
+
+    Ok(match String::from_utf8(bytes) {
+        Ok(text) if !text.contains('\0') => (Rope::from(text), false),
+        Ok(text) => (Rope::from(hex_dump(text.as_bytes())), true),
+        Err(err) => (Rope::from(hex_dump(err.as_bytes())), true),
+    })
+}
+
+/// Format bytes as lines of 16 in hex, alongside their printable ASCII.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (i, line) in bytes.chunks(16).enumerate() {
+        write!(dump, "{:08x}:", i * 16).unwrap();
+
+        for byte in line {
+            write!(dump, " {byte:02x}").unwrap();
+        }
+        dump.push_str(&"   ".repeat(16 - line.len()));
+
+        dump.push_str("  ");
+        dump.extend(line.iter().map(|&byte| match byte {
+            b' '..=b'~' => byte as char,
+            _ => '.',
+        }));
+        dump.push('\n');
+    }
+
+    dump
+}
+
+/// Why input to [`read_rope`] isn't text, with the byte it was found at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NotText {
+    InvalidUtf8(u64),
+    Nul(u64),
+}
+
+impl fmt::Display for NotText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidUtf8(at) => write!(f, "invalid UTF-8 at byte {at}"),
+            Self::Nul(at) => write!(f, "NUL byte at byte {at}"),
+        }
+    }
+}
+
+impl std::error::Error for NotText {}
+
+/// Read text into a rope in chunks, checking that it's valid UTF-8 without
+/// NUL bytes, and failing with [`NotText`] if not. `progress` is called with
+/// the number of bytes read so far after each chunk.
+fn read_rope(mut reader: impl Read, mut progress: impl FnMut(u64)) -> Result<Rope> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    let mut builder = RopeBuilder::new();
+
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut read = 0;
+
+    // Bytes at the start of `buf` that are part of a char split across
+    // chunks.
+    let mut pending = 0;
+
+    loop {
+        let n = reader
+            .read(&mut buf[pending..])
+            .context("couldn't read file")?;
+        if n == 0 {
+            if pending > 0 {
+                return Err(NotText::InvalidUtf8(read - pending as u64).into());
+            }
+            break;
+        }
+
+        let len = pending + n;
+        let chunk_start = read - pending as u64;
+        read += n as u64;
+
+        let text = match std::str::from_utf8(&buf[..len]) {
+            Ok(text) => text,
+            Err(err) if err.error_len().is_none() => {
+                std::str::from_utf8(&buf[..err.valid_up_to()]).expect("valid UTF-8")
+            }
+            Err(err) => {
+                let at = chunk_start + err.valid_up_to() as u64;
+                return Err(NotText::InvalidUtf8(at).into());
+            }
+        };
+
+        if let Some(i) = text.find('\0') {
+            return Err(NotText::Nul(chunk_start + i as u64).into());
+        }
+
+        let valid = text.len();
+        builder.append(text);
+
+        buf.copy_within(valid..len, 0);
+        pending = len - valid;
+
+        progress(read);
+    }
+
+    Ok(builder.build())
+}
+
+/// The closest char boundary at or before `index`, clamped to the rope.
+fn floor_char_boundary(rope: &Rope, index: usize) -> usize {
+    let mut index = index.min(rope.byte_len());
+    while !rope.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+/// The byte index of the first occurrence of `query` in `slice`, going
+/// through its chunks rather than copying it into one string.
+fn find_in_slice(slice: RopeSlice, query: &str) -> Option<usize> {
+    // A match can span chunks, so the end of the text searched so far is kept
+    // to search again along with the next chunk.
+    let mut window = String::new();
+    let mut window_start = 0;
+
+    for chunk in slice.chunks() {
+        window.push_str(chunk);
+        if let Some(index) = window.find(query) {
+            return Some(window_start + index);
+        }
+
+        let mut cut = window.len().saturating_sub(query.len() - 1);
+        while !window.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        window.drain(..cut);
+        window_start += cut;
+    }
+
+    None
+}
+
+fn content_hash(rope: &Rope) -> u64 {
+    // `DefaultHasher` is streaming, so writing the chunks one by one gives the
+    // same result as writing the whole text at once.
+    let mut hasher = DefaultHasher::new();
+    for chunk in rope.chunks() {
+        hasher.write(chunk.as_bytes());
+    }
+    hasher.write_usize(rope.byte_len());
+    hasher.finish()
+}
+
+fn write_file(rope: &Rope, path: &Path, final_newline: FinalNewline) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+
+    for chunk in rope.chunks() {
+        file.write_all(chunk.as_bytes())?;
+    }
+
+    if final_newline == FinalNewline::Ensure && rope.byte_len() > 0 && !rope.has_trailing_newline()
+    {
+        file.write_all(b"\n")?;
+    }
+
+    file.flush()
+}
+
+pub trait RopeExt {
+    /// Whether the last line ends in a newline. An empty rope counts as
+    /// ending in a newline, as it has a single empty line.
+    fn has_trailing_newline(&self) -> bool;
+
+    /// The text of a line, without its line ending. Lines past the end of the
+    /// rope are empty.
+    fn line_text(&self, line: usize) -> Cow<'_, str>;
+}
+
+impl RopeExt for Rope {
+    fn has_trailing_newline(&self) -> bool {
+        match self.chunks().next_back() {
+            Some(chunk) => chunk.ends_with('\n'),
+            None => true,
+        }
+    }
+
+    fn line_text(&self, line: usize) -> Cow<'_, str> {
+        if line >= self.line_len() {
+            return Cow::Borrowed("");
+        }
+
+        let slice = self.line(line);
+        let mut chunks = slice.chunks();
+
+        match (chunks.next(), chunks.next()) {
+            (None, _) => Cow::Borrowed(""),
+            (Some(chunk), None) => Cow::Borrowed(chunk),
+            _ => Cow::Owned(slice.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::fs;
+    use std::rc::Rc;
+
+    use ash_term::units::OffsetUsize;
+    use crop::Rope;
+
+    use crop::RopeBuilder;
+
+    use super::{
+        Diagnostic, DocStats, Document, Edit, FinalNewline, IndentStyle, Motion, RopeExt, Severity,
+        STREAMING_THRESHOLD,
+    };
+
+    fn document(text: &str) -> Document {
+        Document {
+            rope: Rope::from(text),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn visible_lines() {
+        let mut doc = document("zero\none\ntwo\nthree\nfour\n");
+        doc.scroll_offset = OffsetUsize::new(0, 2);
+
+        let lines: Vec<_> = doc
+            .visible_lines(doc.scroll_offset(), OffsetUsize::new(10, 2))
+            .map(|(i, line)| (i, line.to_string()))
+            .collect();
+
+        assert_eq!(lines[0].0, doc.scroll_offset().y);
+        assert_eq!(lines, [(2, "two".to_string()), (3, "three".to_string())]);
+    }
+
+    #[test]
+    fn random_edits() {
+        const STRINGS: &[&str] = &["a", "£", "🐻‍❄️", "\n", "\r\n", "e\u{301}", "hello world"];
+
+        // Simple xorshift, so the test is deterministic without extra dependencies.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % n
+        };
+
+        let mut doc = document("");
+
+        for _ in 0..10_000 {
+            match next(10) {
+                0 => doc.insert_str(STRINGS[next(STRINGS.len())]),
+                1 => doc.insert_str_after(STRINGS[next(STRINGS.len())]),
+                2 => doc.backspace(),
+                3 => doc.delete(),
+                4 => doc.move_left(),
+                5 => doc.move_right(),
+                6 => doc.move_up(),
+                7 => doc.move_down(),
+                8 => doc.move_home(),
+                _ => doc.move_end(),
+            }
+
+            doc.assert_invariants();
+        }
+    }
+
+    #[test]
+    fn wrapped_cursor() {
+        let mut doc = document("0123456789abc\nxyz\n");
+
+        doc.cursor_index = 4;
+        assert_eq!(doc.cursor_offset_wrapped(5), OffsetUsize::new(4, 0));
+
+        doc.cursor_index = 11;
+        assert_eq!(doc.cursor_offset_wrapped(5), OffsetUsize::new(1, 2));
+
+        assert_eq!(doc.line_rows(0, 5), 3);
+        assert_eq!(doc.line_rows(1, 5), 1);
+
+        doc.cursor_index = 16;
+        doc.scroll_to_show_cursor_wrapped(OffsetUsize::new(5, 3));
+        assert_eq!(doc.scroll_offset(), OffsetUsize::new(0, 1));
+    }
+
+    #[test]
+    fn tab_width() {
+        let mut doc = document("\tab\ta\n");
+
+        doc.cursor_index = 1;
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(4, 0));
+
+        doc.cursor_index = 4;
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(8, 0));
+
+        doc.go_to_offset(OffsetUsize::new(2, 0));
+        assert_eq!(doc.cursor_index, 0);
+
+        doc.go_to_offset(OffsetUsize::new(7, 0));
+        assert_eq!(doc.cursor_index, 3);
+    }
+
+    #[test]
+    fn set_tab_width() {
+        let mut doc = document("\tab\n\tcd");
+        doc.cursor_index = 2;
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(5, 0));
+
+        doc.set_tab_width(8);
+        assert_eq!(doc.cursor_index, 2);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(9, 0));
+
+        doc.scroll_to_show_cursor(OffsetUsize::new(6, 2), 0);
+        assert_eq!(doc.scroll_offset(), OffsetUsize::new(4, 0));
+
+        // The column to move to isn't carried over from the old tab width.
+        doc.set_tab_width(2);
+        doc.move_by(Motion::LineDown);
+        assert_eq!(doc.cursor_index, 6);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(3, 1));
+    }
+
+    #[test]
+    fn line_byte_range() {
+        let doc = document("one\ntwo\r\nthree");
+        assert_eq!(doc.line_byte_range(0), Some(0..4));
+        assert_eq!(doc.line_byte_range(1), Some(4..9));
+        assert_eq!(doc.line_byte_range(2), Some(9..14));
+        assert_eq!(doc.line_byte_range(3), None);
+
+        let doc = document("one\ntwo\n");
+        assert_eq!(doc.line_byte_range(1), Some(4..8));
+        assert_eq!(doc.line_byte_range(2), Some(8..8));
+        assert_eq!(doc.line_byte_range(3), None);
+
+        let doc = document("");
+        assert_eq!(doc.line_byte_range(0), Some(0..0));
+        assert_eq!(doc.line_byte_range(1), None);
+    }
+
+    #[test]
+    fn move_home_smart() {
+        let mut doc = document("  \tfoo bar\n   \nbaz");
+        doc.cursor_index = 8;
+
+        doc.move_home_smart();
+        assert_eq!(doc.cursor_index, 3);
+        doc.move_home_smart();
+        assert_eq!(doc.cursor_index, 0);
+        doc.move_home_smart();
+        assert_eq!(doc.cursor_index, 3);
+
+        // A blank line has no text to go to.
+        doc.cursor_index = 13;
+        doc.move_home_smart();
+        assert_eq!(doc.cursor_index, 11);
+
+        // Without indentation, both are the same place.
+        doc.cursor_index = 17;
+        doc.move_home_smart();
+        assert_eq!(doc.cursor_index, 15);
+        doc.move_home_smart();
+        assert_eq!(doc.cursor_index, 15);
+    }
+
+    #[test]
+    fn indent_with_spaces() {
+        let mut doc = document("");
+        doc.set_indent_style(IndentStyle::Spaces(4));
+
+        doc.insert_tab();
+        assert_eq!(doc.rope.to_string(), "    ");
+
+        // Spaces only go up to the next level.
+        doc.insert_str("ab");
+        doc.insert_tab();
+        assert_eq!(doc.rope.to_string(), "    ab  ");
+
+        doc.insert_newline_with_indent();
+        doc.insert_char('c');
+        assert_eq!(doc.rope.to_string(), "    ab  \n    c");
+
+        // Tabs in existing indentation are turned into spaces.
+        let mut doc = document("\t x");
+        doc.set_indent_style(IndentStyle::Spaces(2));
+        doc.cursor_index = 3;
+        doc.insert_newline_with_indent();
+        assert_eq!(doc.rope.to_string(), "\t x\n     ");
+    }
+
+    #[test]
+    fn indent_with_tabs() {
+        let mut doc = document("");
+        doc.set_indent_style(IndentStyle::Tabs);
+
+        doc.insert_tab();
+        doc.insert_char('a');
+        assert_eq!(doc.rope.to_string(), "\ta");
+
+        doc.insert_newline_with_indent();
+        assert_eq!(doc.rope.to_string(), "\ta\n\t");
+
+        // Indentation that isn't a whole number of tabs is made up in spaces.
+        let mut doc = document("      x");
+        doc.set_indent_style(IndentStyle::Tabs);
+        doc.cursor_index = 7;
+        doc.insert_newline_with_indent();
+        assert_eq!(doc.rope.to_string(), "      x\n\t  ");
+    }
+
+    #[test]
+    fn newline_indent_before_cursor() {
+        let mut doc = document("    foo");
+        doc.cursor_index = 2;
+        doc.insert_newline_with_indent();
+
+        assert_eq!(doc.rope.to_string(), "  \n    foo");
+        assert_eq!(doc.cursor_index, 5);
+    }
+
+    #[test]
+    fn indent_and_dedent() {
+        let mut doc = document("foo\n\nbar");
+        doc.cursor_index = 1;
+
+        doc.indent();
+        assert_eq!(doc.rope.to_string(), "    foo\n\nbar");
+        assert_eq!(doc.cursor_index, 5);
+
+        doc.set_indent_style(IndentStyle::Tabs);
+        doc.indent();
+        assert_eq!(doc.rope.to_string(), "\t    foo\n\nbar");
+
+        assert!(doc.dedent());
+        assert!(doc.dedent());
+        assert_eq!(doc.rope.to_string(), "foo\n\nbar");
+        assert_eq!(doc.cursor_index, 1);
+        assert!(!doc.dedent());
+
+        // Empty lines aren't indented.
+        doc.cursor_index = 4;
+        doc.indent();
+        assert_eq!(doc.rope.to_string(), "foo\n\nbar");
+    }
+
+    #[test]
+    fn indent_selection() {
+        let mut doc = document("one\n\ttwo\n\nthree\nfour");
+        doc.cursor_index = 1;
+        doc.selection_anchor = Some(15);
+
+        // The selection ends at the start of the last line, so that's left.
+        doc.indent();
+        assert_eq!(
+            doc.rope.to_string(),
+            "    one\n    \ttwo\n\n    three\nfour"
+        );
+        assert_eq!(doc.selection(), Some(5..27));
+
+        assert!(doc.dedent());
+        assert!(doc.dedent());
+        assert_eq!(doc.rope.to_string(), "one\ntwo\n\nthree\nfour");
+        assert_eq!(doc.selection(), Some(1..14));
+
+        assert!(!doc.dedent());
+    }
+
+    #[test]
+    fn delete_pair() {
+        for text in ["a()", "a[]", "a\"\""] {
+            let mut doc = document(text);
+            doc.cursor_index = 2;
+
+            assert!(doc.delete_pair());
+            assert_eq!(doc.rope.to_string(), "a");
+            assert_eq!(doc.cursor_index, 1);
+        }
+
+        for (text, cursor) in [("(a)", 2), ("(]", 1), ("()", 0), ("()", 2)] {
+            let mut doc = document(text);
+            doc.cursor_index = cursor;
+
+            assert!(!doc.delete_pair());
+            assert_eq!(doc.rope.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn word_at_cursor() {
+        let mut doc = document("let foo_bar2 =  café;");
+
+        // inside
+        doc.cursor_index = 7;
+        assert_eq!(doc.word_at_cursor(), Some((4..12, "foo_bar2".to_string())));
+
+        // start
+        doc.cursor_index = 4;
+        assert_eq!(doc.word_at_cursor(), Some((4..12, "foo_bar2".to_string())));
+
+        // end
+        doc.cursor_index = 12;
+        assert_eq!(doc.word_at_cursor(), Some((4..12, "foo_bar2".to_string())));
+
+        // whitespace
+        doc.cursor_index = 15;
+        assert_eq!(doc.word_at_cursor(), None);
+
+        // multi-byte
+        doc.cursor_index = 18;
+        assert_eq!(doc.word_at_cursor(), Some((16..21, "café".to_string())));
+    }
+
+    #[test]
+    fn select_word() {
+        let mut doc = document("let foo_bar2 = 3;");
+        doc.cursor_index = 7;
+
+        doc.select_word();
+        assert_eq!(doc.selection(), Some(4..12));
+        assert_eq!(doc.cursor_index, 12);
+
+        doc.insert_char('x');
+        assert_eq!(doc.selection(), None);
+
+        // on whitespace
+        doc.cursor_index = 14;
+        doc.select_word();
+        assert_eq!(doc.selection(), None);
+        assert_eq!(doc.cursor_index, 14);
+    }
+
+    #[test]
+    fn move_page() {
+        let text: String = (0..100).map(|i| format!("line {i}\n")).collect();
+        let mut doc = document(&text);
+        doc.cursor_index = 3;
+
+        doc.move_page(1, 20);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(3, 20));
+        assert_eq!(doc.scroll_offset().y, 20);
+
+        doc.move_page(2, 20);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(3, 60));
+        assert_eq!(doc.scroll_offset().y, 60);
+
+        doc.move_page(-1, 20);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(3, 40));
+        assert_eq!(doc.scroll_offset().y, 40);
+    }
+
+    #[test]
+    fn center_cursor() {
+        let text: String = (0..100).map(|i| format!("line {i}\n")).collect();
+        let mut doc = document(&text);
+
+        // near the top, clamped
+        doc.go_to_offset(OffsetUsize::new(0, 2));
+        doc.center_cursor(10);
+        assert_eq!(doc.scroll_offset().y, 0);
+
+        // middle
+        doc.go_to_offset(OffsetUsize::new(0, 50));
+        doc.center_cursor(10);
+        assert_eq!(doc.scroll_offset().y, 45);
+
+        // near the end
+        doc.go_to_offset(OffsetUsize::new(0, 99));
+        doc.center_cursor(10);
+        assert_eq!(doc.scroll_offset().y, 94);
+    }
+
+    #[test]
+    fn move_page_preserves_target_column() {
+        let mut doc = document("long line\n\nshort\nanother long line\n");
+        doc.cursor_index = 7;
+
+        doc.move_page(1, 1);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 1));
+
+        doc.move_page(1, 2);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(7, 3));
+    }
+
+    #[test]
+    fn move_down_after_multiline_insert() {
+        let mut doc = document("long line\n\nanother long line\n");
+        doc.cursor_index = 7;
+
+        // Leaves a target column of 7 on the empty line.
+        doc.move_down();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 1));
+
+        doc.insert_str("ab\ncd");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(2, 2));
+
+        doc.move_down();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(2, 3));
+    }
+
+    #[test]
+    fn replace_char() {
+        let mut doc = document("abc\ndef");
+        doc.cursor_index = 1;
+
+        doc.replace_char('x');
+        assert_eq!(doc.rope.to_string(), "axc\ndef");
+        assert_eq!(doc.cursor_index, 2);
+
+        doc.replace_char('y');
+        doc.replace_char('z');
+        assert_eq!(doc.rope.to_string(), "axyz\ndef");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(4, 0));
+    }
+
+    #[test]
+    fn replace_char_end_of_file() {
+        let mut doc = document("ab");
+        doc.cursor_index = 2;
+
+        doc.replace_char('c');
+        assert_eq!(doc.rope.to_string(), "abc");
+    }
+
+    #[test]
+    fn duplicate_line() {
+        let mut doc = document("one\ntwo\nthree\n");
+        doc.cursor_index = 5;
+
+        doc.duplicate_line();
+        assert_eq!(doc.rope.to_string(), "one\ntwo\ntwo\nthree\n");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(1, 2));
+
+        let mut doc = document("one\ntwo");
+        doc.cursor_index = 5;
+
+        doc.duplicate_line();
+        assert_eq!(doc.rope.to_string(), "one\ntwo\ntwo");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(1, 2));
+    }
+
+    #[test]
+    fn delete_line() {
+        let mut doc = document("one\ntwo\nthree");
+        doc.cursor_index = 5;
+
+        doc.delete_line();
+        assert_eq!(doc.rope.to_string(), "one\nthree");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 1));
+
+        doc.delete_line();
+        assert_eq!(doc.rope.to_string(), "one");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 0));
+
+        doc.delete_line();
+        assert_eq!(doc.rope.to_string(), "");
+        assert_eq!(doc.cursor_index, 0);
+    }
+
+    #[test]
+    fn join_lines() {
+        let mut doc = document("    one\n    two\nthree\n");
+        doc.cursor_index = 2;
+
+        doc.join_lines();
+        assert_eq!(doc.rope.to_string(), "    one two\nthree\n");
+        assert_eq!(doc.cursor_index, 7);
+
+        doc.join_lines();
+        assert_eq!(doc.rope.to_string(), "    one two three\n");
+    }
+
+    #[test]
+    fn join_lines_end_of_file() {
+        let mut doc = document("one\ntwo");
+        doc.cursor_index = 5;
+
+        doc.join_lines();
+        assert_eq!(doc.rope.to_string(), "one\ntwo");
+        assert_eq!(doc.cursor_index, 5);
+    }
+
+    #[test]
+    fn scrolloff() {
+        let text: String = (0..20).map(|i| format!("{i}\n")).collect();
+        let mut doc = document(&text);
+        let size = OffsetUsize::new(10, 10);
+
+        for _ in 0..10 {
+            doc.move_down();
+            doc.scroll_to_show_cursor(size, 3);
+        }
+
+        // Three lines of context below the cursor.
+        assert_eq!(doc.cursor_offset().y, 10);
+        assert_eq!(doc.scroll_offset.y, 4);
+
+        for _ in 0..4 {
+            doc.move_up();
+            doc.scroll_to_show_cursor(size, 3);
+        }
+
+        // Three lines of context above the cursor.
+        assert_eq!(doc.cursor_offset().y, 6);
+        assert_eq!(doc.scroll_offset.y, 3);
+    }
+
+    #[test]
+    fn scrolloff_document_edges() {
+        let text: String = (0..20).map(|i| format!("{i}\n")).collect();
+        let mut doc = document(&text);
+        let size = OffsetUsize::new(10, 10);
+
+        doc.move_down();
+        doc.scroll_to_show_cursor(size, 3);
+        assert_eq!(doc.scroll_offset.y, 0);
+
+        for _ in 0..30 {
+            doc.move_down();
+            doc.scroll_to_show_cursor(size, 3);
+        }
+
+        // The cursor reaches the last line without scrolling past the end.
+        assert_eq!(doc.cursor_offset().y, 20);
+        assert_eq!(doc.scroll_offset.y, 11);
+
+        for _ in 0..30 {
+            doc.move_up();
+            doc.scroll_to_show_cursor(size, 3);
+        }
+
+        assert_eq!(doc.cursor_offset().y, 0);
+        assert_eq!(doc.scroll_offset.y, 0);
+    }
+
+    #[test]
+    fn scrolloff_horizontal() {
+        let mut doc = document(&"a".repeat(30));
+        let size = OffsetUsize::new(10, 10);
+
+        for _ in 0..10 {
+            doc.move_right();
+            doc.scroll_to_show_cursor(size, 3);
+        }
+
+        // Three columns of context to the right of the cursor.
+        assert_eq!(doc.cursor_offset().x, 10);
+        assert_eq!(doc.scroll_offset.x, 4);
+
+        for _ in 0..4 {
+            doc.move_left();
+            doc.scroll_to_show_cursor(size, 3);
+        }
+
+        // Three columns of context to the left of the cursor.
+        assert_eq!(doc.cursor_offset().x, 6);
+        assert_eq!(doc.scroll_offset.x, 3);
+    }
+
+    #[test]
+    fn cursor_line_col() {
+        let mut doc = document("héllo\nwörld\n");
+        doc.cursor_index = "héllo\nwö".len();
+        assert_eq!(doc.cursor_line_col(), (1, 2));
+
+        doc.set_cursor_line_col(0, 3);
+        assert_eq!(doc.cursor_index, "hél".len());
+        assert_eq!(doc.cursor_line_col(), (0, 3));
+
+        // Clamped to the end of the line.
+        doc.set_cursor_line_col(1, 100);
+        assert_eq!(doc.cursor_line_col(), (1, 5));
+
+        // Clamped to the last line.
+        doc.set_cursor_line_col(100, 100);
+        assert_eq!(doc.cursor_line_col(), (2, 0));
+    }
+
+    #[test]
+    fn find_next() {
+        let mut doc = document("foo bar foo baz");
+
+        assert_eq!(doc.find("foo", 0), Some(0..3));
+        assert_eq!(doc.find("foo", 1), Some(8..11));
+        assert_eq!(doc.find("qux", 0), None);
+
+        assert_eq!(doc.find_next("foo"), Some(8));
+        assert_eq!(doc.cursor_index, 8);
+
+        // Wraps around to the start.
+        assert_eq!(doc.find_next("foo"), Some(0));
+        assert_eq!(doc.cursor_index, 0);
+    }
+
+    #[test]
+    fn find_across_chunks() {
+        let text: String = (0..5000).map(|i| format!("{i}é,")).collect();
+        let doc = document(&text);
+
+        let mut boundary = 0;
+        for chunk in doc.rope.chunks().take(4) {
+            boundary += chunk.len();
+
+            let mut start = boundary - 3;
+            while !text.is_char_boundary(start) {
+                start -= 1;
+            }
+            let mut end = boundary + 3;
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
+            let query = &text[start..end];
+
+            assert_eq!(
+                doc.find(query, 0).map(|range| range.start),
+                text.find(query)
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn find_next_regex() {
+        let mut doc = document("abc 123\nfoo 456");
+
+        assert_eq!(doc.find_next_regex(r"\b[0-9]+").unwrap(), Some(4));
+        assert_eq!(doc.find_next_regex(r"\b[0-9]+").unwrap(), Some(12));
+
+        // Wraps around to the start.
+        assert_eq!(doc.find_next_regex(r"(?m)^\w+").unwrap(), Some(0));
+        assert_eq!(doc.find_next_regex(r"(?m)^\w+").unwrap(), Some(8));
+
+        assert_eq!(doc.find_next_regex(r"xyz").unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn find_next_regex_invalid() {
+        let mut doc = document("abc");
+        assert!(doc.find_next_regex(r"[a-").is_err());
+        assert_eq!(doc.cursor_index, 0);
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ash_editor_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn open_binary() {
+        let path = temp_path("open_binary");
+
+        fs::write(&path, b"text").unwrap();
+        assert!(!Document::new(Some(path.clone())).unwrap().is_read_only());
+
+        fs::write(&path, b"caf\xe9 \x00\x01").unwrap();
+        let mut doc = Document::new(Some(path.clone())).unwrap();
+        assert!(doc.is_read_only());
+        assert_eq!(
+            doc.rope.to_string(),
+            format!(
+                "00000000: 63 61 66 e9 20 00 01{}  caf. ..\n",
+                " ".repeat(27)
+            )
+        );
+
+        // Saving would write the hex dump over the file.
+        assert!(doc.save_file().is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"caf\xe9 \x00\x01");
+
+        // It can't be made writable either.
+        assert!(doc.set_read_only(false).is_err());
+        assert!(doc.is_read_only());
+        doc.read_only = false;
+        assert!(doc.save_file().is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"caf\xe9 \x00\x01");
+
+        // NUL bytes count as binary even when valid UTF-8.
+        fs::write(&path, b"a\x00b").unwrap();
+        assert!(Document::new(Some(path.clone())).unwrap().is_read_only());
+
+        // Reloading checks again.
+        fs::write(&path, b"fixed").unwrap();
+        doc.reload().unwrap();
+        assert!(!doc.is_read_only());
+        assert_eq!(doc.rope.to_string(), "fixed");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn open_streaming() {
+        let path = temp_path("open_streaming");
+
+        // Multi-byte chars end up split across chunks.
+        let text = "héllo wörld\n".repeat(300_000);
+        std::fs::write(&path, &text).unwrap();
+
+        let mut progress = vec![];
+        let doc = Document::open_streaming(path.clone(), |read| progress.push(read)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(doc.rope.byte_len(), text.len());
+        assert_eq!(doc.rope, text.as_str());
+        assert!(progress.is_sorted());
+        assert_eq!(progress.last(), Some(&(text.len() as u64)));
+    }
+
+    #[test]
+    fn open_streaming_binary() {
+        let path = temp_path("open_streaming_binary");
+
+        // Both are past the threshold, so they're streamed.
+        let mut invalid = b"a".repeat(STREAMING_THRESHOLD as usize);
+        invalid.extend_from_slice(b"\xffdef");
+        let mut nul = b"a".repeat(STREAMING_THRESHOLD as usize);
+        nul.extend_from_slice(b"\x00def");
+
+        for bytes in [invalid, nul] {
+            std::fs::write(&path, &bytes).unwrap();
+
+            let mut doc = Document::new(Some(path.clone())).unwrap();
+            assert!(doc.is_read_only());
+            assert!(doc.rope.to_string().starts_with("00000000: 61 61"));
+
+            // It's the same when reloaded.
+            doc.reload().unwrap();
+            assert!(doc.is_read_only());
+
+            assert!(doc.save_file().is_err());
+            assert_eq!(std::fs::read(&path).unwrap(), bytes);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_without_trailing_newline() {
+        let path = temp_path("save_without_trailing_newline");
+        std::fs::write(&path, "no trailing newline").unwrap();
+
+        let mut doc = Document::new(Some(path.clone())).unwrap();
+        doc.save_file().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"no trailing newline");
+
+        doc.set_final_newline(FinalNewline::Ensure);
+        doc.save_file().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"no trailing newline\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn modified() {
+        let path = temp_path("modified");
+        std::fs::write(&path, "text").unwrap();
+
+        let mut doc = Document::new(Some(path.clone())).unwrap();
+        assert!(!doc.is_modified());
+
+        doc.move_left();
+        assert!(!doc.is_modified());
+
+        doc.backspace();
+        assert!(doc.is_modified());
+
+        doc.insert_char('x');
+        doc.backspace();
+        assert!(doc.is_modified());
+
+        doc.save_file().unwrap();
+        assert!(!doc.is_modified());
+        assert_eq!(std::fs::read(&path).unwrap(), b"tet");
+
+        doc.insert_char('x');
+        assert!(doc.is_modified());
+        doc.backspace();
+        assert!(!doc.is_modified());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn save_without_path() {
+        let mut doc = document("text");
+        assert_eq!(doc.save_file().unwrap_err().to_string(), "no file name");
+    }
+
+    #[test]
+    fn stats() {
+        let doc = document("Hello, world!\n  foo-bar   baz.\n\n£5 each\n");
+
+        assert_eq!(
+            doc.stats(),
+            DocStats {
+                bytes: 41,
+                chars: 40,
+                words: 6,
+                lines: 4,
+            }
+        );
+
+        assert_eq!(document("").stats(), DocStats::default());
+    }
+
+    #[test]
+    fn stats_across_chunks() {
+        let text = "word ".repeat(10_000);
+        let doc = document(&text);
+        assert!(doc.rope.chunks().count() > 1);
+
+        let stats = doc.stats();
+        assert_eq!(stats.words, 10_000);
+        assert_eq!(stats.chars, text.len());
+    }
+
+    #[test]
+    fn chunks() {
+        let text = "line of text\n".repeat(10_000);
+        let mut doc = document(&text);
+        doc.insert_str("£");
+
+        let chunks: Vec<&str> = doc.chunks().collect();
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), format!("£{text}"));
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut doc = document("foo.bar  baz\n  qux");
+
+        let right = |doc: &mut Document| {
+            doc.move_by(Motion::WordRight);
+            doc.cursor_index
+        };
+        assert_eq!(right(&mut doc), 3);
+        assert_eq!(right(&mut doc), 4);
+        assert_eq!(right(&mut doc), 9);
+        assert_eq!(right(&mut doc), 15);
+        assert_eq!(right(&mut doc), 18);
+        assert_eq!(right(&mut doc), 18);
+
+        let left = |doc: &mut Document| {
+            doc.move_by(Motion::WordLeft);
+            doc.cursor_index
+        };
+        assert_eq!(left(&mut doc), 15);
+        assert_eq!(left(&mut doc), 9);
+        assert_eq!(left(&mut doc), 4);
+        assert_eq!(left(&mut doc), 3);
+        assert_eq!(left(&mut doc), 0);
+        assert_eq!(left(&mut doc), 0);
+    }
+
+    #[test]
+    fn move_over_emoji() {
+        // A ZWJ sequence, and a flag made of two regional indicators.
+        for emoji in ["🐻\u{200d}❄\u{fe0f}", "🇬🇧"] {
+            let text = format!("a{emoji}b");
+            let mut doc = document(&text);
+            doc.move_right();
+
+            doc.move_right();
+            assert_eq!(doc.cursor_index, 1 + emoji.len(), "{emoji}");
+
+            doc.move_left();
+            assert_eq!(doc.cursor_index, 1, "{emoji}");
+        }
+    }
+
+    #[test]
+    fn backspace_emoji() {
+        for emoji in ["🐻\u{200d}❄\u{fe0f}", "🇬🇧"] {
+            let text = format!("a{emoji}b");
+            let mut doc = document(&text);
+            doc.set_cursor_index(1 + emoji.len());
+
+            doc.backspace();
+            assert_eq!(doc.rope.to_string(), "ab", "{emoji}");
+            assert_eq!(doc.cursor_index, 1, "{emoji}");
+        }
+    }
+
+    #[test]
+    fn word_motion_combining_char() {
+        let mut doc = document("cafe\u{301} au lait");
+        doc.move_by(Motion::WordRight);
+        assert_eq!(doc.cursor_offset().x, 5);
+    }
+
+    #[test]
+    fn content_hash() {
+        let text = "the quick brown fox\n".repeat(1000);
+
+        let mut builder = RopeBuilder::new();
+        for line in text.split_inclusive('\n') {
+            builder.append(line);
+        }
+        let mut a = Document {
+            rope: builder.build(),
+            ..Default::default()
+        };
+        let b = document(&text);
+
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert_ne!(a.content_hash(), document("other").content_hash());
+
+        let hash = a.content_hash();
+        a.set_cursor_index(100);
+        a.insert_str("jumps");
+        assert_ne!(a.content_hash(), hash);
+
+        for _ in 0..5 {
+            a.backspace();
+        }
+        assert_eq!(a.content_hash(), hash);
+    }
+
+    #[test]
+    fn insert_char_paired() {
+        let mut doc = document("");
+
+        doc.insert_char_paired('(');
+        assert_eq!(doc.rope.to_string(), "()");
+        assert_eq!(doc.cursor_index, 1);
+
+        doc.insert_char_paired('[');
+        doc.insert_char_paired('x');
+        assert_eq!(doc.rope.to_string(), "([x])");
+        assert_eq!(doc.cursor_index, 3);
+
+        doc.insert_char_paired(']');
+        doc.insert_char_paired(')');
+        assert_eq!(doc.rope.to_string(), "([x])");
+        assert_eq!(doc.cursor_index, 5);
+
+        // No closer to move over.
+        doc.insert_char_paired(')');
+        assert_eq!(doc.rope.to_string(), "([x]))");
+        assert_eq!(doc.cursor_index, 6);
+    }
+
+    #[test]
+    fn insert_char_paired_quotes() {
+        let mut doc = document("don");
+        doc.set_cursor_index(3);
+
+        doc.insert_char_paired('\'');
+        doc.insert_char_paired('t');
+        assert_eq!(doc.rope.to_string(), "don't");
+
+        doc.insert_char_paired(' ');
+        doc.insert_char_paired('"');
+        assert_eq!(doc.rope.to_string(), "don't \"\"");
+        doc.insert_char_paired('"');
+        assert_eq!(doc.rope.to_string(), "don't \"\"");
+        assert_eq!(doc.cursor_index, 8);
+    }
+
+    #[test]
+    fn insert_char_paired_wraps_selection() {
+        let mut doc = document("hello world");
+        doc.set_cursor_index(2);
+        doc.select_word();
+
+        doc.insert_char_paired('"');
+        assert_eq!(doc.rope.to_string(), "\"hello\" world");
+        assert_eq!(doc.selection(), Some(1..6));
+        assert_eq!(doc.cursor_index, 6);
+    }
+
+    #[test]
+    fn line_text() {
+        let rope = Rope::from("unix\nwindows\r\nlast");
+        assert_eq!(rope.line_text(0), "unix");
+        assert_eq!(rope.line_text(1), "windows");
+        assert_eq!(rope.line_text(2), "last");
+        assert_eq!(rope.line_text(3), "");
+
+        let rope = Rope::from("trailing\n");
+        assert_eq!(rope.line_text(0), "trailing");
+        assert_eq!(rope.line_text(1), "");
+    }
+
+    #[test]
+    fn line_text_across_chunks() {
+        let line = "a".repeat(10_000);
+        let rope = Rope::from(format!("{line}\r\n"));
+        assert!(rope.chunks().count() > 1);
+        assert_eq!(rope.line_text(0), line);
+    }
+
+    #[test]
+    fn virtual_final_line() {
+        let mut doc = document("ab\ncd\n");
+        doc.move_right();
+
+        doc.move_down();
+        doc.move_down();
+        assert_eq!(doc.cursor_index, 6);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 2));
+
+        doc.move_end();
+        assert_eq!(doc.cursor_index, 6);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 2));
+
+        doc.move_down();
+        assert_eq!(doc.cursor_index, 6);
+
+        doc.go_to_offset(OffsetUsize::new(5, 2));
+        assert_eq!(doc.cursor_index, 6);
+        doc.go_to_offset(OffsetUsize::new(0, 3));
+        assert_eq!(doc.cursor_index, 6);
+    }
+
+    #[test]
+    fn virtual_final_line_keeps_column() {
+        let mut doc = document("ab\ncd\n");
+        doc.move_down();
+        doc.move_right();
+
+        // Moving onto the empty final line and back keeps the column, as it
+        // would for any other short line.
+        doc.move_down();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 2));
+        doc.move_up();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(1, 1));
+    }
+
+    #[test]
+    fn virtual_final_line_virtual_edit() {
+        let mut doc = document("ab\n");
+        doc.set_virtual_edit(true);
+        doc.move_right();
+        doc.move_right();
+        doc.move_right();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(3, 0));
+
+        doc.move_down();
+        assert_eq!(doc.cursor_index, 3);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(3, 1));
+    }
+
+    #[test]
+    fn move_end_crlf() {
+        let mut doc = document("one\r\ntwo");
+        doc.move_end();
+        assert_eq!(doc.cursor_index, 3);
+    }
+
+    #[test]
+    fn grapheme_boundaries() {
+        // The polar bear is four codepoints, 13 bytes.
+        let doc = document("a🐻‍❄️e\u{301}");
+
+        assert_eq!(doc.grapheme_at(0).as_deref(), Some("a"));
+        assert_eq!(doc.grapheme_at(1).as_deref(), Some("🐻‍❄️"));
+        assert_eq!(doc.grapheme_at(14).as_deref(), Some("e\u{301}"));
+        assert_eq!(doc.grapheme_at(17), None);
+
+        assert_eq!(doc.next_grapheme_boundary(0), 1);
+        assert_eq!(doc.next_grapheme_boundary(1), 14);
+        assert_eq!(doc.next_grapheme_boundary(14), 17);
+        assert_eq!(doc.next_grapheme_boundary(17), 17);
+
+        assert_eq!(doc.prev_grapheme_boundary(17), 14);
+        assert_eq!(doc.prev_grapheme_boundary(14), 1);
+        assert_eq!(doc.prev_grapheme_boundary(1), 0);
+        assert_eq!(doc.prev_grapheme_boundary(0), 0);
+    }
+
+    #[test]
+    fn backspace_indent_aware() {
+        let mut doc = document("x\n        y");
+        doc.set_cursor_index(10);
+
+        doc.backspace_indent_aware();
+        assert_eq!(doc.rope.to_string(), "x\n    y");
+        assert_eq!(doc.cursor_index, 6);
+
+        // Back to the previous tab stop from part way through an indent.
+        doc.insert_str("  ");
+        doc.backspace_indent_aware();
+        assert_eq!(doc.rope.to_string(), "x\n    y");
+
+        doc.backspace_indent_aware();
+        assert_eq!(doc.rope.to_string(), "x\ny");
+
+        // At the start of the line, join with the previous line as usual.
+        doc.backspace_indent_aware();
+        assert_eq!(doc.rope.to_string(), "xy");
+    }
+
+    #[test]
+    fn backspace_indent_aware_after_text() {
+        let mut doc = document("    a    ");
+        doc.set_cursor_index(9);
+
+        doc.backspace_indent_aware();
+        assert_eq!(doc.rope.to_string(), "    a   ");
+    }
+
+    #[test]
+    fn set_scroll_offset_clamps() {
+        let text: String = (0..20).map(|i| format!("{i}\n")).collect();
+        let mut doc = document(&text);
+        let size = OffsetUsize::new(10, 10);
+
+        doc.set_scroll_offset(OffsetUsize::new(0, 5), size);
+        assert_eq!(doc.scroll_offset, OffsetUsize::new(0, 5));
+
+        // 21 lines, including the empty one after the trailing newline.
+        doc.set_scroll_offset(OffsetUsize::new(100, 100), size);
+        assert_eq!(doc.scroll_offset, OffsetUsize::new(0, 11));
+
+        let mut doc = document("short\nthis line is longer than the view");
+        doc.set_scroll_offset(OffsetUsize::new(100, 100), size);
+        assert_eq!(doc.scroll_offset, OffsetUsize::new(24, 0));
+    }
+
+    #[test]
+    fn marks() {
+        let mut doc = document("one\ntwo\nthree");
+        doc.set_cursor_index(5);
+        doc.set_mark('a');
+
+        doc.set_cursor_index(9);
+        doc.insert_str("big ");
+        assert!(doc.jump_to_mark('a'));
+        assert_eq!(doc.cursor_index, 5);
+
+        assert!(!doc.jump_to_mark('b'));
+        assert_eq!(doc.cursor_index, 5);
+    }
+
+    #[test]
+    fn marks_shift_on_insert() {
+        let mut doc = document("one two");
+        doc.set_cursor_index(4);
+        doc.set_mark('a');
+        doc.set_cursor_index(7);
+        doc.set_mark('b');
+
+        doc.set_cursor_index(0);
+        doc.insert_str("zero ");
+        assert!(doc.jump_to_mark('a'));
+        assert_eq!(doc.cursor_index, 9);
+
+        // Text inserted at a mark goes after it, so the mark stays where it
+        // is. Marks further on move along with their text.
+        doc.insert_str("big ");
+        assert!(doc.jump_to_mark('a'));
+        assert_eq!(doc.cursor_index, 9);
+        assert!(doc.jump_to_mark('b'));
+        assert_eq!(doc.cursor_index, 16);
+    }
+
+    #[test]
+    fn marks_shift_on_delete() {
+        let mut doc = document("one two three");
+        doc.set_cursor_index(5);
+        doc.set_mark('a');
+        doc.set_cursor_index(8);
+        doc.set_mark('b');
+
+        // Delete "ne t", which contains mark a.
+        doc.set_cursor_index(5);
+        for _ in 0..4 {
+            doc.backspace();
+        }
+        assert_eq!(doc.rope.to_string(), "owo three");
+
+        assert!(doc.jump_to_mark('a'));
+        assert_eq!(doc.cursor_index, 1);
+        assert!(doc.jump_to_mark('b'));
+        assert_eq!(doc.cursor_index, 4);
+    }
+
+    #[test]
+    fn replace_all() {
+        let mut doc = document("let foo = 1;\nfoo += foo;\nbar(foo)");
+        doc.set_cursor_index(doc.rope.byte_len());
+        doc.set_mark('a');
+
+        assert_eq!(doc.replace_all("foo", "value"), 4);
+        assert_eq!(
+            doc.rope.to_string(),
+            "let value = 1;\nvalue += value;\nbar(value)"
+        );
+        assert_eq!(doc.cursor_index, doc.rope.byte_len());
+        assert!(doc.jump_to_mark('a'));
+        assert_eq!(doc.cursor_index, doc.rope.byte_len());
+
+        assert_eq!(doc.replace_all("missing", "x"), 0);
+        assert_eq!(doc.replace_all("", "x"), 0);
+    }
+
+    #[test]
+    fn replace_all_positions_after_match() {
+        let mut doc = document("foo bar");
+        doc.set_cursor_index(3);
+        doc.set_mark('a');
+        doc.set_diagnostics(vec![
+            Diagnostic {
+                range: 3..7,
+                severity: Severity::Error,
+            },
+            Diagnostic {
+                range: 0..3,
+                severity: Severity::Warning,
+            },
+        ]);
+
+        assert_eq!(doc.replace_all("foo", "x"), 1);
+        assert_eq!(doc.rope.to_string(), "x bar");
+        assert_eq!(doc.cursor_index, 1);
+        assert_eq!(doc.diagnostics()[0].range, 1..5);
+        assert_eq!(doc.diagnostics()[1].range, 0..1);
+
+        doc.set_cursor_index(0);
+        assert!(doc.jump_to_mark('a'));
+        assert_eq!(doc.cursor_index, 1);
+    }
+
+    #[test]
+    fn replace_all_cursor_inside_match() {
+        let mut doc = document("a long word");
+        doc.set_cursor_index(4);
+
+        assert_eq!(doc.replace_all("long", "x"), 1);
+        assert_eq!(doc.rope.to_string(), "a x word");
+        assert_eq!(doc.cursor_index, 2);
+    }
+
+    #[test]
+    fn on_change() {
+        let edits = Rc::new(RefCell::new(vec![]));
+
+        let mut doc = document("one\ntwo");
+        doc.set_on_change(Box::new({
+            let edits = edits.clone();
+            move |edit| edits.borrow_mut().push(edit.clone())
+        }));
+
+        doc.insert_str("1 ");
+        doc.delete();
+        doc.backspace();
+        doc.join_lines();
+
+        let edit = |range, text: &str| Edit {
+            range,
+            text: text.to_owned(),
+        };
+        assert_eq!(
+            *edits.borrow(),
+            [
+                edit(0..0, "1 "),
+                edit(2..3, ""),
+                edit(1..2, ""),
+                edit(3..4, ""),
+                edit(3..3, " "),
+            ]
+        );
+        assert_eq!(doc.rope.to_string(), "1ne two");
+    }
+
+    #[test]
+    fn diagnostics_shift() {
+        let mut doc = document("let foo = 1;");
+        doc.set_diagnostics(vec![Diagnostic {
+            range: 4..7,
+            severity: Severity::Error,
+        }]);
+
+        doc.insert_str("// ");
+        assert_eq!(doc.diagnostics()[0].range, 7..10);
+
+        doc.set_cursor_index(9);
+        doc.insert_char('o');
+        assert_eq!(doc.diagnostics()[0].range, 7..11);
+
+        // Deleting the start of the range keeps what's left of it.
+        doc.set_cursor_index(8);
+        doc.backspace();
+        doc.backspace();
+        assert_eq!(doc.rope.to_string(), "// letooo = 1;");
+        assert_eq!(doc.diagnostics()[0].range, 6..9);
+
+        doc.delete_line();
+        assert_eq!(doc.diagnostics()[0].range, 0..0);
+    }
+
+    #[test]
+    fn modify_number() {
+        let mut doc = document("9");
+        assert!(doc.modify_number(1));
+        assert_eq!(doc.rope.to_string(), "10");
+        assert_eq!(doc.cursor_index, 1);
+
+        let mut doc = document("0");
+        assert!(doc.modify_number(-1));
+        assert_eq!(doc.rope.to_string(), "-1");
+        assert_eq!(doc.cursor_index, 1);
+
+        assert!(doc.modify_number(5));
+        assert_eq!(doc.rope.to_string(), "4");
+        assert_eq!(doc.cursor_index, 0);
+    }
+
+    #[test]
+    fn modify_number_search() {
+        // The number after the cursor, on the same line.
+        let mut doc = document("x = -5;\n7");
+        assert!(doc.modify_number(1));
+        assert_eq!(doc.rope.to_string(), "x = -4;\n7");
+        assert_eq!(doc.cursor_index, 5);
+
+        // Anywhere inside the number.
+        let mut doc = document("a 1234 b");
+        doc.set_cursor_index(4);
+        assert!(doc.modify_number(10));
+        assert_eq!(doc.rope.to_string(), "a 1244 b");
+
+        // Leading zeros keep the width.
+        let mut doc = document("file009");
+        assert!(doc.modify_number(1));
+        assert_eq!(doc.rope.to_string(), "file010");
+    }
+
+    #[test]
+    fn modify_number_none() {
+        let mut doc = document("abc\n1");
+        assert!(!doc.modify_number(1));
+        assert_eq!(doc.rope.to_string(), "abc\n1");
+        assert_eq!(doc.cursor_index, 0);
+        assert!(!doc.is_modified());
+
+        let mut doc = document("1 abc");
+        doc.set_cursor_index(2);
+        assert!(!doc.modify_number(1));
+    }
+
+    #[test]
+    fn sticky_line_end() {
+        let mut doc = document("a long line\nab\n\nmedium\nlonger line");
+        doc.move_end();
+        assert_eq!(doc.cursor_line_col(), (0, 11));
+
+        for (line, col) in [(1, 2), (2, 0), (3, 6), (4, 11)] {
+            doc.move_down();
+            assert_eq!(doc.cursor_line_col(), (line, col));
+        }
+
+        doc.move_up();
+        assert_eq!(doc.cursor_line_col(), (3, 6));
+
+        // Moving horizontally stops sticking to the end.
+        doc.move_left();
+        doc.move_down();
+        assert_eq!(doc.cursor_line_col(), (4, 5));
+        doc.move_up();
+        doc.move_up();
+        assert_eq!(doc.cursor_line_col(), (2, 0));
+        doc.move_up();
+        assert_eq!(doc.cursor_line_col(), (1, 2));
+    }
+
+    #[test]
+    fn from_reader() {
+        let doc = Document::from_reader("piped\ninput £\n".as_bytes()).unwrap();
+
+        assert_eq!(doc.rope.to_string(), "piped\ninput £\n");
+        assert_eq!(doc.path(), None);
+        assert!(!doc.is_modified());
+
+        let err = Document::from_reader(&b"ok\xff"[..]).err().unwrap();
+        assert_eq!(err.to_string(), "invalid UTF-8 at byte 2");
+        let err = Document::from_reader(&b"ok\x00"[..]).err().unwrap();
+        assert_eq!(err.to_string(), "NUL byte at byte 2");
+    }
+
+    #[test]
+    fn reload() {
+        let path = temp_path("reload");
+        std::fs::write(&path, "one\ntwo\nthree").unwrap();
+
+        let mut doc = Document::new(Some(path.clone())).unwrap();
+        doc.insert_str(" four");
+        doc.set_mark('a');
+        assert!(doc.is_modified());
+
+        std::fs::write(&path, "changed £").unwrap();
+        doc.reload().unwrap();
+
+        assert_eq!(doc.rope.to_string(), "changed £");
+        assert!(!doc.is_modified());
+        // Clamped to the end of the shorter text.
+        assert_eq!(doc.cursor_index, doc.rope.byte_len());
+        assert!(doc.jump_to_mark('a'));
+        assert_eq!(doc.cursor_index, doc.rope.byte_len());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(doc.reload().is_err());
+        assert!(Document::default().reload().is_err());
+    }
+
+    #[test]
+    fn reload_char_boundary() {
+        let path = temp_path("reload_char_boundary");
+        std::fs::write(&path, "abc").unwrap();
+
+        let mut doc = Document::new(Some(path.clone())).unwrap();
+        doc.set_cursor_index(2);
+
+        std::fs::write(&path, "a£").unwrap();
+        doc.reload().unwrap();
+        assert_eq!(doc.cursor_index, 1);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn restore_position_clamped() {
+        let mut doc = document("a£\nb");
+        doc.restore_position(2, OffsetUsize::new(0, 1));
+        assert_eq!(doc.cursor_index, 1);
+        assert_eq!(doc.scroll_offset, OffsetUsize::new(0, 1));
+
+        doc.restore_position(100, OffsetUsize::new(0, 100));
+        assert_eq!(doc.cursor_index, 5);
+        assert_eq!(doc.scroll_offset, OffsetUsize::new(0, 1));
+    }
+
+    #[test]
+    fn virtual_edit() {
+        let mut doc = document("a long line\nab\nlast");
+        doc.set_virtual_edit(true);
+        doc.move_down();
+
+        for _ in 0..5 {
+            doc.move_right();
+        }
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(5, 1));
+        assert_eq!(doc.cursor_index, 14);
+
+        // Padded with spaces when text is typed.
+        doc.insert_char('x');
+        assert_eq!(doc.rope.to_string(), "a long line\nab   x\nlast");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(6, 1));
+    }
+
+    #[test]
+    fn virtual_edit_vertical() {
+        let mut doc = document("a long line\nab\nlast");
+        doc.set_virtual_edit(true);
+        doc.set_cursor_index(8);
+
+        doc.move_down();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(8, 1));
+
+        doc.move_left();
+        doc.move_down();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(7, 2));
+
+        // Moving back onto text, then other motions, leave virtual space.
+        doc.move_up();
+        doc.move_up();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(7, 0));
+        doc.move_down();
+        doc.move_by(Motion::LineStart);
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 1));
+
+        // Without it, the cursor stays on the text.
+        doc.set_virtual_edit(false);
+        doc.move_end();
+        doc.move_right();
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(0, 2));
+    }
+
+    #[test]
+    fn virtual_edit_backspace() {
+        let mut doc = document("ab");
+        doc.set_virtual_edit(true);
+        doc.move_end();
+        doc.move_right();
+        doc.move_right();
+
+        doc.delete();
+        doc.backspace();
+        assert_eq!(doc.rope.to_string(), "ab");
+        assert_eq!(doc.cursor_offset(), OffsetUsize::new(3, 0));
+
+        doc.backspace();
+        doc.backspace();
+        assert_eq!(doc.rope.to_string(), "a");
     }
 }
@@ -1,12 +1,17 @@
+use std::collections::HashMap;
 use std::ops::{ControlFlow, Range};
+use std::time::{Duration, Instant};
 
-use crate::action::{Action, KeyMap};
-use crate::document::{Document, RopeExt};
+use crate::action::{format_keys, Action, KeyMap};
+use crate::document::{
+    grapheme_width, wrap_graphemes, Document, FinalNewline, IndentStyle, Motion, RopeExt, Severity,
+};
+use crate::state::{FileState, StateStore};
 use anyhow::Result;
 use ash_term::buffer::{BufferView, Cell};
-use ash_term::event::Event;
-use ash_term::style::{CursorShape, CursorStyle, Style, Weight};
-use ash_term::units::{OffsetU16, OffsetUsize};
+use ash_term::event::{Event, KeyCode, KeyEvent};
+use ash_term::style::{Color, CursorShape, CursorStyle, Style, Weight};
+use ash_term::units::{OffsetU16, OffsetUsize, Rect};
 use crop::Rope;
 use unicode_width::UnicodeWidthStr;
 
@@ -15,32 +20,417 @@ pub enum Mode {
     #[default]
     Normal,
     Insert,
+    Replace,
+    Command,
+    Search,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    Info,
+    Error,
+}
+
+const SELECTION_STYLE: Style = Style {
+    bg: Color::Blue,
+    ..Style::EMPTY
+};
+
+const SEARCH_MATCH_STYLE: Style = Style {
+    bg: Color::Yellow,
+    ..Style::EMPTY
+};
+
+fn diagnostic_style(severity: Severity) -> Style {
+    let fg = match severity {
+        Severity::Error => Color::Red,
+        Severity::Warning => Color::Yellow,
+        Severity::Info => Color::Blue,
+        Severity::Hint => Color::Cyan,
+    };
+
+    Style {
+        fg,
+        underline: true,
+        ..Style::EMPTY
+    }
+}
+
+const EOF_MARKER_STYLE: Style = Style {
+    weight: Weight::Dim,
+    ..Style::EMPTY
+};
+
+struct Pane {
+    doc_index: usize,
+    view_rect: Rect,
+    /// Scroll offset, in cells. The focused pane scrolls to show the cursor,
+    /// and the others stay where they are, even if they show the same
+    /// document.
+    scroll_offset: OffsetUsize,
+}
+
+impl Pane {
+    fn new(doc_index: usize, scroll_offset: OffsetUsize) -> Self {
+        Self {
+            doc_index,
+            view_rect: Rect::ZERO,
+            scroll_offset,
+        }
+    }
 }
 
-#[derive(Default)]
 pub struct Editor {
-    document: Document,
+    /// Open documents. There is always at least one.
+    documents: Vec<Document>,
+    active: usize,
+
+    /// Panes, laid out side by side. The first pane has focus.
+    panes: Vec<Pane>,
+
     mode: Mode,
     keymap: KeyMap,
+    pending_keys: Vec<KeyEvent>,
+
+    /// The command being typed in command mode, or the query in search mode.
+    command: String,
+
+    /// The cursor index and scroll offset when search mode was entered, to
+    /// restore if the search is cancelled.
+    search_origin: Option<(usize, OffsetUsize)>,
+    /// The match previewed while typing a search.
+    search_match: Option<Range<usize>>,
+    last_search: String,
+
+    /// The actions making up the last change, for repeating with `.`. A
+    /// change is either a single edit in normal mode, or everything from
+    /// entering insert or replace mode to leaving it.
+    last_change: Vec<Action>,
+    /// The actions so far of a change being made in insert or replace mode.
+    change: Vec<Action>,
+
+    /// Recorded macros, by register.
+    macros: HashMap<char, Vec<Event>>,
+    /// The register being recorded into, and the events recorded so far.
+    recording: Option<(char, Vec<Event>)>,
+    /// The registers of the macros being played, innermost last.
+    playing: Vec<char>,
+
+    /// A message shown on the status line until the next key press.
+    message: Option<(String, MessageKind)>,
+
+    /// Whether the key bindings overlay is shown. Any key dismisses it.
+    help: bool,
+
+    /// Whether to flash the status line when an action can't be performed.
+    visual_bell: bool,
+    /// Whether the bell has rung since the last draw.
+    bell: bool,
+
+    /// Size of the text view, as of the last draw.
+    view_size: OffsetUsize,
+
+    line_numbers: bool,
+    wrap: bool,
+
+    /// Lines and columns of context to keep around the cursor when scrolling.
+    scrolloff: usize,
+
+    /// The cursor style to use in each mode.
+    cursor_styles: HashMap<Mode, CursorStyle>,
+
+    /// Whether to show tabs and trailing spaces.
+    render_whitespace: bool,
+    /// Whether to mark the rows past the end of the document with `~`.
+    eof_markers: bool,
+
+    /// Whether to close brackets and quotes automatically in insert mode.
+    auto_pairs: bool,
+    /// Whether backspace in indentation deletes back to the previous tab
+    /// stop.
+    smart_backspace: bool,
+
+    /// How long to wait after the last input before saving modified
+    /// documents. `None` disables autosave.
+    autosave_interval: Option<Duration>,
+    /// When the last input was, if there hasn't been an autosave since.
+    last_input: Option<Instant>,
+
+    /// The focused document, its pane and its scroll offset, as of the last
+    /// draw.
+    last_view: Option<(usize, Rect, OffsetUsize)>,
+    /// The rows the last draw scrolled, and by how many lines.
+    scrolled_rows: Option<(Range<u16>, i32)>,
+
+    /// Whether the whole screen should be redrawn, rather than just the cells
+    /// that changed.
+    force_redraw: bool,
+    /// Whether anything has changed since the last draw.
+    dirty: bool,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            documents: vec![Document::default()],
+            active: 0,
+
+            panes: vec![Pane::new(0, OffsetUsize::ZERO)],
+
+            mode: Mode::default(),
+            keymap: KeyMap::default(),
+            pending_keys: vec![],
+
+            command: String::new(),
+
+            search_origin: None,
+            search_match: None,
+            last_search: String::new(),
+
+            last_change: vec![],
+            change: vec![],
+
+            macros: HashMap::new(),
+            recording: None,
+            playing: vec![],
+
+            message: None,
+
+            help: false,
+
+            visual_bell: true,
+            bell: false,
+
+            view_size: OffsetUsize::ZERO,
+
+            line_numbers: true,
+            wrap: false,
+
+            scrolloff: 3,
+
+            cursor_styles: default_cursor_styles(),
+
+            render_whitespace: false,
+            eof_markers: true,
+
+            auto_pairs: true,
+            smart_backspace: true,
+
+            autosave_interval: None,
+            last_input: None,
+
+            last_view: None,
+            scrolled_rows: None,
+
+            force_redraw: false,
+            dirty: true,
+        }
+    }
 }
 
 impl Editor {
     pub fn new(document: Document) -> Self {
         Self {
-            document,
+            documents: vec![document],
             ..Default::default()
         }
     }
 
+    /// Open another document, without switching to it.
+    pub fn add_document(&mut self, document: Document) {
+        self.documents.push(document);
+    }
+
+    /// Restore where each open file was left, from `store`.
+    pub fn load_state(&mut self, store: &StateStore) {
+        for document in &mut self.documents {
+            let Some(state) = document.path().and_then(|path| store.get(path)) else {
+                continue;
+            };
+
+            document.restore_position(state.cursor_index, state.scroll_offset);
+        }
+    }
+
+    /// Record where each open file was left, in `store`.
+    pub fn save_state(&self, store: &mut StateStore) {
+        for document in &self.documents {
+            let Some(path) = document.path() else {
+                continue;
+            };
+
+            let state = FileState {
+                cursor_index: document.cursor_index(),
+                scroll_offset: document.scroll_offset(),
+            };
+            store.insert(path, state);
+        }
+    }
+
+    fn document(&self) -> &Document {
+        &self.documents[self.active]
+    }
+
+    /// Returns whether the whole screen should be redrawn, resetting the flag.
+    pub fn take_force_redraw(&mut self) -> bool {
+        std::mem::take(&mut self.force_redraw)
+    }
+
+    /// The rows of the screen the last draw scrolled vertically, and by how
+    /// many lines, with a positive count meaning the text moved up. The
+    /// terminal can scroll these rows rather than redrawing them.
+    ///
+    /// This is only set when a single pane without wrapping was scrolled, so
+    /// that whole rows moved together.
+    pub fn scrolled_rows(&self) -> Option<(Range<u16>, i32)> {
+        self.scrolled_rows.clone()
+    }
+
+    /// Returns whether the editor needs to be redrawn.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+
+    pub fn set_message(&mut self, message: impl Into<String>, kind: MessageKind) {
+        self.message = Some((message.into(), kind));
+    }
+
+    /// Signal that an action couldn't be performed. The bell is shown on the
+    /// next draw.
+    pub fn bell(&mut self) {
+        self.bell = true;
+        self.dirty |= self.visual_bell;
+    }
+
+    /// Called every frame. Returns an action to perform that isn't triggered
+    /// by input, such as an autosave.
+    pub fn tick(&mut self, now: Instant) -> Option<Action> {
+        let interval = self.autosave_interval?;
+        let last_input = self.last_input?;
+
+        if now.saturating_duration_since(last_input) < interval {
+            return None;
+        }
+
+        // Only save once per idle period.
+        self.last_input = None;
+
+        self.documents
+            .iter()
+            .any(|document| document.path().is_some() && document.is_modified())
+            .then_some(Action::AutoSave)
+    }
+
     pub fn handle_event(&mut self, event: Event) -> ControlFlow<Result<()>> {
-        if let Some(action) = self.keymap.get_action(self.mode, event) {
-            self.handle_action(action)
+        match event {
+            // Save when switching away, rather than waiting for the idle timer.
+            Event::FocusLost if self.autosave_interval.is_some() => {
+                return self.handle_action(Action::AutoSave);
+            }
+            Event::FocusGained | Event::FocusLost => return ControlFlow::Continue(()),
+            _ => {}
+        }
+
+        if self.help && matches!(event, Event::Key(_)) {
+            self.help = false;
+            self.dirty = true;
+            return ControlFlow::Continue(());
+        }
+
+        // Only record keys that were typed, not ones from a macro being played.
+        if self.recording.is_some() && self.playing.is_empty() {
+            let is_stop_key = matches!(
+                event,
+                Event::Key(KeyEvent {
+                    key_code: KeyCode::Char('q'),
+                    modifiers,
+                }) if modifiers.is_empty()
+            );
+
+            if is_stop_key && self.mode == Mode::Normal && self.pending_keys.is_empty() {
+                let (register, events) = self.recording.take().expect("recording");
+                self.macros.insert(register, events);
+                return ControlFlow::Continue(());
+            }
+
+            if let Some((_, events)) = &mut self.recording {
+                events.push(event.clone());
+            }
+        }
+
+        let is_key = matches!(event, Event::Key(_));
+        if is_key && self.message.take().is_some() {
+            self.dirty = true;
+        }
+
+        if matches!(event, Event::Key(_) | Event::Paste(_)) {
+            self.last_input = Some(Instant::now());
+        }
+
+        if let Some(action) = self
+            .keymap
+            .get_action(self.mode, &mut self.pending_keys, event)
+        {
+            let was_inserting = self.is_inserting();
+            let flow = self.handle_action(action.clone());
+            self.record_change(action, was_inserting);
+
+            #[cfg(debug_assertions)]
+            self.document().assert_invariants();
+
+            flow
         } else {
+            // The keys pressed don't match any binding. Keys that are bound to
+            // `Action::Unbound` are disabled on purpose, so they don't get here.
+            if is_key && self.pending_keys.is_empty() {
+                self.bell();
+            }
+
             ControlFlow::Continue(())
         }
     }
 
-    fn handle_action(&mut self, action: Action) -> ControlFlow<Result<()>> {
+    pub fn handle_action(&mut self, action: Action) -> ControlFlow<Result<()>> {
+        let document = &mut self.documents[self.active];
+
+        if let Some(motion) = action.motion() {
+            // Compare offsets, as moving in virtual space doesn't change the index.
+            let cursor_offset = document.cursor_offset();
+            document.move_by(motion);
+
+            // Jumps to a fixed position aren't an error if the cursor's already there.
+            let is_jump = matches!(
+                motion,
+                Motion::LineStart
+                    | Motion::SmartLineStart
+                    | Motion::LineEnd
+                    | Motion::DocumentStart
+                    | Motion::DocumentEnd
+            );
+            if document.cursor_offset() != cursor_offset {
+                self.dirty = true;
+            } else if !is_jump {
+                self.bell();
+            }
+
+            return ControlFlow::Continue(());
+        }
+
+        // Disabled keys do nothing, so there's nothing to redraw.
+        if !matches!(action, Action::Unbound) {
+            self.dirty = true;
+        }
+
+        // Every edit goes through here, including those in combos and repeats.
+        if action.is_edit() && document.is_read_only() {
+            self.set_message("buffer is read-only", MessageKind::Error);
+            return ControlFlow::Continue(());
+        }
+
         match action {
             Action::Combo(actions) => {
                 for action in actions {
@@ -48,158 +438,1025 @@ impl Editor {
                 }
             }
 
-            Action::InsertChar(ch) => self.document.insert_char(ch),
-            Action::InsertCharAfter(ch) => self.document.insert_char_after(ch),
+            Action::InsertChar(ch) if self.auto_pairs => document.insert_char_paired(ch),
+            Action::InsertChar(ch) => document.insert_char(ch),
+            Action::InsertCharAfter(ch) => document.insert_char_after(ch),
 
-            Action::InsertString(s) => self.document.insert_str(&s),
-            Action::InsertStringAfter(s) => self.document.insert_str_after(&s),
+            Action::ReplaceChar(ch) => document.replace_char(ch),
 
-            Action::Backspace => self.document.backspace(),
-            Action::Delete => self.document.delete(),
+            Action::InsertString(s) => document.insert_str(&s),
+            Action::InsertStringAfter(s) => document.insert_str_after(&s),
 
-            Action::MoveLeft => self.document.move_left(),
-            Action::MoveRight => self.document.move_right(),
-            Action::MoveUp => self.document.move_up(),
-            Action::MoveDown => self.document.move_down(),
+            Action::Backspace => {
+                // Backspacing inside an empty pair deletes its closer too.
+                if !(self.auto_pairs && document.delete_pair()) {
+                    match self.smart_backspace {
+                        true => document.backspace_indent_aware(),
+                        false => document.backspace(),
+                    }
+                }
+            }
+            Action::Delete => document.delete(),
 
-            Action::MoveHome => self.document.move_home(),
-            Action::MoveEnd => self.document.move_end(),
+            Action::DuplicateLine => document.duplicate_line(),
+            Action::DeleteLine => document.delete_line(),
+            Action::JoinLines => document.join_lines(),
 
-            Action::SetMode(mode) => self.mode = mode,
+            Action::InsertNewline => document.insert_newline_with_indent(),
+            Action::InsertTab if document.selection().is_some() => document.indent(),
+            Action::InsertTab => document.insert_tab(),
 
-            Action::Save => self.document.save_file(),
-            Action::Quit => return ControlFlow::Break(Ok(())),
-        }
+            Action::Indent => document.indent(),
+            Action::Dedent => {
+                if !document.dedent() {
+                    self.bell();
+                }
+            }
 
-        ControlFlow::Continue(())
-    }
-}
+            Action::ModifyNumber(delta) => {
+                if !document.modify_number(delta) {
+                    self.bell();
+                }
+            }
 
-impl Editor {
-    pub fn draw(&mut self, buffer: &mut BufferView) {
-        self.document.scroll_to_show_cursor(buffer.size().into());
+            Action::Move(_)
+            | Action::MoveLeft
+            | Action::MoveRight
+            | Action::MoveUp
+            | Action::MoveDown
+            | Action::MoveHome
+            | Action::MoveEnd => unreachable!("motions are handled above"),
 
-        let gutter_width = self.draw_gutter(buffer);
+            Action::GoToLine(line) => document.set_cursor_line_col(line, 0),
 
-        let mut edit_view = buffer.view(gutter_width as u16.., .., true);
-        self.draw_text(&mut edit_view);
-        self.draw_cursor(&mut edit_view);
-    }
+            Action::PageUp => document.move_page(-1, self.view_size.y),
+            Action::PageDown => document.move_page(1, self.view_size.y),
 
-    fn draw_gutter(&self, buffer: &mut BufferView) -> usize {
-        const GUTTER_STYLE: Style = Style {
-            weight: Weight::Dim,
-            ..Style::EMPTY
-        };
+            Action::CenterCursor => document.center_cursor(self.view_size.y),
 
-        let gutters = Gutters::new(self.document.rope(), "", "  ", "~");
-        let max_width = gutters.max_width();
+            Action::SetMark(name) => document.set_mark(name),
+            Action::JumpToMark(name) => {
+                if !document.jump_to_mark(name) {
+                    self.bell();
+                }
+            }
 
-        for (y, gutter) in gutters
-            .skip(self.document.scroll_offset().y)
-            .take(buffer.size().y as usize)
-            .enumerate()
-        {
-            buffer.draw_text(OffsetU16::new(0, y as u16), &gutter, GUTTER_STYLE);
-        }
+            Action::SelectWord => document.select_word(),
+            Action::ClearSelection => document.clear_selection(),
 
-        max_width
-    }
+            Action::ToggleLineNumbers => {
+                self.line_numbers = !self.line_numbers;
+                self.force_redraw = true;
+            }
+            Action::ToggleWrap => {
+                self.wrap = !self.wrap;
+                self.force_redraw = true;
+            }
+            Action::SetLineNumbers(line_numbers) => {
+                self.line_numbers = line_numbers;
+                self.force_redraw = true;
+            }
+            Action::SetWrap(wrap) => {
+                self.wrap = wrap;
+                self.force_redraw = true;
+            }
+            Action::ToggleAutoPairs => self.auto_pairs = !self.auto_pairs,
+            Action::ToggleVirtualEdit => {
+                let virtual_edit = !document.virtual_edit();
+                document.set_virtual_edit(virtual_edit);
+            }
 
-    fn draw_text(&self, buffer: &mut BufferView) {
-        let size: OffsetUsize = buffer.size().into();
-        let scroll_offset = self.document.scroll_offset();
+            Action::NextBuffer => self.active = (self.active + 1) % self.documents.len(),
+            Action::PrevBuffer => {
+                self.active = (self.active + self.documents.len() - 1) % self.documents.len();
+            }
 
-        for (y, line) in self
-            .document
-            .rope()
-            .lines()
-            .skip(scroll_offset.y)
-            .take(size.y)
-            .enumerate()
-        {
-            let mut x = 0;
-            for grapheme in line.graphemes() {
-                if x >= scroll_offset.x {
-                    let column = x - scroll_offset.x;
+            Action::Split => {
+                if self.panes.len() == 1 {
+                    let doc_index = (self.active + 1) % self.documents.len();
+                    let scroll_offset = self.documents[doc_index].scroll_offset();
+                    self.panes.push(Pane::new(doc_index, scroll_offset));
+                }
+            }
+            Action::CloseSplits => self.panes.truncate(1),
 
-                    if column >= size.x {
-                        break;
+            Action::SetMode(mode) => {
+                if matches!(mode, Mode::Command | Mode::Search) {
+                    self.command.clear();
+                }
+
+                if mode == Mode::Search {
+                    self.search_origin = Some((document.cursor_index(), document.scroll_offset()));
+                }
+
+                self.mode = mode;
+            }
+
+            Action::CommandInsert(ch) => {
+                self.command.push(ch);
+                self.update_search();
+            }
+            Action::CommandBackspace => {
+                self.command.pop();
+                self.update_search();
+            }
+            Action::CommandExecute if self.mode == Mode::Search => {
+                // The cursor is already on the previewed match.
+                self.mode = Mode::Normal;
+                self.last_search = std::mem::take(&mut self.command);
+                self.search_origin = None;
+                self.search_match = None;
+            }
+            Action::CommandExecute => {
+                self.mode = Mode::Normal;
+
+                let command = std::mem::take(&mut self.command);
+                if let Some(action) = parse_command(&command) {
+                    return self.handle_action(action);
+                } else if !command.trim().is_empty() {
+                    self.set_message(
+                        format!("unknown command: {}", command.trim()),
+                        MessageKind::Error,
+                    );
+                }
+            }
+
+            Action::CancelSearch => {
+                if let Some((cursor_index, scroll_offset)) = self.search_origin.take() {
+                    document.set_cursor_index(cursor_index);
+                    document.set_scroll_offset(scroll_offset, self.view_size);
+                }
+
+                self.mode = Mode::Normal;
+                self.search_match = None;
+            }
+            Action::SearchNext => {
+                document.find_next(&self.last_search);
+            }
+            #[cfg(feature = "regex")]
+            Action::SearchRegex(pattern) => {
+                if let Err(err) = document.find_next_regex(&pattern) {
+                    self.set_message(err.to_string(), MessageKind::Error);
+                }
+            }
+
+            Action::RepeatChange => {
+                for action in self.last_change.clone() {
+                    self.handle_action(action)?;
+                }
+            }
+
+            Action::RecordMacro(register) => {
+                if self.playing.is_empty() {
+                    self.recording = Some((register, vec![]));
+                }
+            }
+            Action::PlayMacro(register) => {
+                // Don't let a macro play itself, directly or indirectly.
+                let events = match self.macros.get(&register) {
+                    Some(events) if !self.playing.contains(&register) => events.clone(),
+                    _ => {
+                        self.bell();
+                        return ControlFlow::Continue(());
+                    }
+                };
+
+                self.playing.push(register);
+                let flow = events
+                    .into_iter()
+                    .try_for_each(|event| self.handle_event(event));
+                self.playing.pop();
+
+                return flow;
+            }
+
+            Action::ReplaceAll(from, to) => match document.replace_all(&from, &to) {
+                0 => self.set_message(format!("pattern not found: {from}"), MessageKind::Error),
+                1 => self.set_message("1 substitution", MessageKind::Info),
+                n => self.set_message(format!("{n} substitutions"), MessageKind::Info),
+            },
+
+            Action::ShowStats => {
+                let stats = document.stats();
+                let message = format!(
+                    "{} lines, {} words, {} chars, {} bytes",
+                    stats.lines, stats.words, stats.chars, stats.bytes
+                );
+                self.set_message(message, MessageKind::Info);
+            }
+
+            Action::ShowHelp => self.help = !self.help,
+
+            Action::Save => match document.save_file() {
+                Ok(()) => {
+                    let path = document.path().expect("saved without a path").display();
+                    let message = format!("\"{path}\" written");
+                    self.set_message(message, MessageKind::Info);
+                }
+                Err(err) => self.set_message(format!("{err:#}"), MessageKind::Error),
+            },
+            Action::Reload if document.is_modified() => {
+                self.set_message(
+                    "no write since last change (add ! to override)",
+                    MessageKind::Error,
+                );
+            }
+            Action::Reload | Action::ForceReload => match document.reload() {
+                Ok(()) => {
+                    let path = document.path().expect("reloaded without a path").display();
+                    let message = format!("\"{path}\" reloaded");
+                    self.set_message(message, MessageKind::Info);
+                }
+                Err(err) => self.set_message(format!("{err:#}"), MessageKind::Error),
+            },
+            Action::AutoSave => {
+                let documents = self.documents.iter_mut();
+                for document in documents.filter(|document| document.path().is_some()) {
+                    if !document.is_modified() {
+                        continue;
                     }
 
-                    buffer[[column as u16, y as u16]] =
-                        Some(Cell::empty().with_grapheme(&grapheme));
+                    if let Err(err) = document.save_file() {
+                        let path = document.path().expect("has a path").display();
+                        let message = format!("couldn't autosave \"{path}\": {err:#}");
+                        self.message = Some((message, MessageKind::Error));
+                        break;
+                    }
                 }
+            }
+            Action::SetTabWidth(width) => document.set_tab_width(width),
+            Action::SetReadOnly(read_only) => {
+                if let Err(err) = document.set_read_only(read_only) {
+                    self.set_message(format!("{err:#}"), MessageKind::Error);
+                }
+            }
+            Action::SetFixEol(fix) => document.set_final_newline(match fix {
+                true => FinalNewline::Ensure,
+                false => FinalNewline::Preserve,
+            }),
+            Action::SetExpandTab(expand) => {
+                let style = match (expand, document.indent_style()) {
+                    // Keep the indent width if it's already spaces.
+                    (true, IndentStyle::Spaces(width)) => IndentStyle::Spaces(width),
+                    (true, IndentStyle::Tabs) => IndentStyle::Spaces(document.tab_width()),
+                    (false, _) => IndentStyle::Tabs,
+                };
+                document.set_indent_style(style);
+            }
 
-                x += grapheme.width();
+            Action::SetAutosave(interval) => {
+                self.autosave_interval = interval;
+                self.last_input = None;
             }
+
+            Action::Quit if self.documents.iter().any(Document::is_modified) => {
+                self.set_message(
+                    "no write since last change (add ! to override)",
+                    MessageKind::Error,
+                );
+            }
+            Action::Quit | Action::ForceQuit => return ControlFlow::Break(Ok(())),
+
+            Action::Unbound => {}
         }
+
+        ControlFlow::Continue(())
+    }
+}
+
+impl Editor {
+    fn is_inserting(&self) -> bool {
+        matches!(self.mode, Mode::Insert | Mode::Replace)
     }
 
-    fn draw_cursor(&self, buffer: &mut BufferView) {
-        // If we support cursors being offscreen, we can't use saturating sub.
-        let cursor = self
-            .document
-            .cursor_offset()
-            .saturating_sub(self.document.scroll_offset());
+    /// Keep track of the last change, given an action that was just
+    /// performed.
+    fn record_change(&mut self, action: Action, was_inserting: bool) {
+        match (was_inserting, self.is_inserting()) {
+            (false, false) => {
+                if action.is_edit() {
+                    self.last_change = vec![action];
+                }
+            }
+            (false, true) => self.change = vec![action],
+            (true, true) => self.change.push(action),
+            (true, false) => {
+                self.change.push(action);
 
-        if cursor.cmp_lt(buffer.size().into()).both() {
-            buffer.set_cursor(Some(OffsetU16::from(cursor)));
+                // Don't count entering and leaving insert mode without
+                // typing anything as a change.
+                if self.change.iter().any(Action::is_edit) {
+                    self.last_change = std::mem::take(&mut self.change);
+                } else {
+                    self.change.clear();
+                }
+            }
         }
+    }
 
-        let style = match self.mode {
-            Mode::Normal => CursorStyle {
-                shape: CursorShape::Block,
-                blinking: false,
-            },
-            Mode::Insert => CursorStyle {
-                shape: CursorShape::Bar,
-                blinking: true,
-            },
+    /// Preview the first match of the search query from where the search
+    /// started, moving the cursor to it.
+    fn update_search(&mut self) {
+        let Some((origin, _)) = self.search_origin else {
+            return;
         };
 
-        buffer.set_cursor_style(style);
+        let document = &mut self.documents[self.active];
+
+        self.search_match = document.find(&self.command, origin);
+        document.set_cursor_index(match &self.search_match {
+            Some(range) => range.start,
+            None => origin,
+        });
     }
 }
 
-struct Gutters<'a> {
-    lines: Range<usize>,
-    emit_blank: bool,
-
-    max_width: usize,
+fn default_cursor_styles() -> HashMap<Mode, CursorStyle> {
+    let block = CursorStyle {
+        shape: CursorShape::Block,
+        blinking: false,
+    };
+    let bar = CursorStyle {
+        shape: CursorShape::Bar,
+        blinking: true,
+    };
+    let underscore = CursorStyle {
+        shape: CursorShape::Underscore,
+        blinking: true,
+    };
 
-    prefix: &'a str,
-    postfix: &'a str,
-    blank: &'a str,
+    HashMap::from([
+        (Mode::Normal, block),
+        (Mode::Insert, bar),
+        (Mode::Replace, underscore),
+        (Mode::Command, bar),
+        (Mode::Search, bar),
+    ])
 }
 
-impl<'a> Gutters<'a> {
-    fn new(rope: &Rope, prefix: &'a str, postfix: &'a str, blank: &'a str) -> Self {
-        let len = rope.line_len();
+/// Parse a command typed in command mode into an action.
+fn parse_command(command: &str) -> Option<Action> {
+    #[cfg(feature = "regex")]
+    if let Some(pattern) = command.trim_start().strip_prefix("re ") {
+        return Some(Action::SearchRegex(pattern.to_owned()));
+    }
 
-        let max_width = (len.checked_ilog10().unwrap_or_default() as usize + 1).max(blank.width());
+    if let Some(action) = parse_substitute(command.trim()) {
+        return Some(action);
+    }
 
-        Self {
-            lines: 0..len,
-            emit_blank: rope.has_trailing_newline(),
+    match command.trim() {
+        "w" => Some(Action::Save),
+        "e" => Some(Action::Reload),
+        "e!" => Some(Action::ForceReload),
+        "q" => Some(Action::Quit),
+        "q!" => Some(Action::ForceQuit),
 
-            max_width,
+        "stats" => Some(Action::ShowStats),
+        "h" | "help" => Some(Action::ShowHelp),
 
-            prefix,
-            postfix,
-            blank,
+        "autosave off" => Some(Action::SetAutosave(None)),
+        command if command.starts_with("autosave ") => command["autosave ".len()..]
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .map(|secs| Action::SetAutosave(Some(Duration::from_secs(secs)))),
+
+        command if command.starts_with("set ") => parse_set(command["set ".len()..].trim()),
+
+        "bn" => Some(Action::NextBuffer),
+        "bp" => Some(Action::PrevBuffer),
+
+        "vs" | "vsplit" => Some(Action::Split),
+        "only" => Some(Action::CloseSplits),
+
+        command => command
+            .parse::<usize>()
+            .ok()
+            .map(|line| Action::GoToLine(line.saturating_sub(1))),
+    }
+}
+
+/// Parse the option of a `:set` command.
+fn parse_set(option: &str) -> Option<Action> {
+    match option {
+        "expandtab" | "et" => Some(Action::SetExpandTab(true)),
+        "noexpandtab" | "noet" => Some(Action::SetExpandTab(false)),
+        "readonly" | "ro" => Some(Action::SetReadOnly(true)),
+        "noreadonly" | "noro" => Some(Action::SetReadOnly(false)),
+        "fixeol" | "fixendofline" => Some(Action::SetFixEol(true)),
+        "nofixeol" | "nofixendofline" => Some(Action::SetFixEol(false)),
+        "number" | "nu" => Some(Action::SetLineNumbers(true)),
+        "nonumber" | "nonu" => Some(Action::SetLineNumbers(false)),
+        "wrap" => Some(Action::SetWrap(true)),
+        "nowrap" => Some(Action::SetWrap(false)),
+
+        option => {
+            let (name, value) = option.split_once('=')?;
+            match name {
+                "tabstop" | "ts" => value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&width| width > 0)
+                    .map(Action::SetTabWidth),
+                _ => None,
+            }
         }
     }
+}
 
-    fn max_width(&self) -> usize {
-        self.max_width + self.prefix.width() + self.postfix.width()
+/// Parse a substitute command of the form `%s/from/to/`, where the `/` can be
+/// any character and the trailing one is optional.
+fn parse_substitute(command: &str) -> Option<Action> {
+    let rest = command.strip_prefix("%s")?;
+    let delimiter = rest.chars().next().filter(|ch| !ch.is_alphanumeric())?;
+
+    let mut parts = rest[delimiter.len_utf8()..].split(delimiter);
+    let from = parts.next()?;
+    let to = parts.next().unwrap_or_default();
+
+    // Allow the `g` flag, as all occurrences are always replaced.
+    if !matches!(parts.next(), None | Some("" | "g")) || parts.next().is_some() {
+        return None;
     }
 
-    fn next_with(&mut self, f: impl Fn(&mut Self) -> Option<usize>) -> Option<String> {
-        if let Some(line) = f(self) {
-            return Some(format!(
-                "{}{:>w$}{}",
-                self.prefix,
+    Some(Action::ReplaceAll(from.to_owned(), to.to_owned()))
+}
+
+impl Editor {
+    pub fn draw(&mut self, buffer: &mut BufferView) {
+        let height = buffer.size().y.saturating_sub(1);
+
+        self.draw_panes(&mut buffer.view(.., ..height, true));
+        self.draw_status(&mut buffer.view(.., height.., true));
+
+        let view = (
+            self.active,
+            self.panes[0].view_rect,
+            self.panes[0].scroll_offset,
+        );
+        self.scrolled_rows = match self.last_view.replace(view) {
+            Some((doc_index, rect, scroll_offset))
+                if self.panes.len() == 1
+                    && !self.wrap
+                    && (doc_index, rect, scroll_offset.x) == (view.0, view.1, view.2.x) =>
+            {
+                let delta = view.2.y as i64 - scroll_offset.y as i64;
+                i32::try_from(delta)
+                    .ok()
+                    .filter(|&delta| delta != 0)
+                    .map(|delta| (rect.start.y..rect.end.y, delta))
+            }
+            _ => None,
+        };
+
+        if self.help {
+            buffer.dim();
+            self.draw_help(buffer);
+        }
+
+        if std::mem::take(&mut self.bell) && self.visual_bell {
+            Self::draw_bell(&mut buffer.view(.., height.., false));
+
+            // Redraw again to clear the bell.
+            self.dirty = true;
+        }
+    }
+
+    /// Flash the status line.
+    fn draw_bell(buffer: &mut BufferView) {
+        const BELL_STYLE: Style = Style {
+            fg: Color::Black,
+            bg: Color::White,
+            ..Style::EMPTY
+        };
+
+        for x in 0..buffer.size().x {
+            let cell = buffer[[x, 0]].take().unwrap_or_else(Cell::empty);
+            let style = cell.style().patch(BELL_STYLE);
+            buffer[[x, 0]] = Some(cell.with_style(style));
+        }
+    }
+
+    /// Draw the key bindings for the current mode in a box in the middle of
+    /// the screen.
+    fn draw_help(&self, buffer: &mut BufferView) {
+        const KEYS_STYLE: Style = Style {
+            weight: Weight::Bold,
+            ..Style::EMPTY
+        };
+
+        let bindings: Vec<_> = self
+            .keymap
+            .bindings_for_mode(self.mode)
+            .into_iter()
+            .map(|(keys, action)| (format_keys(keys), action.to_string()))
+            .collect();
+
+        let keys_width = bindings.iter().map(|(keys, _)| keys.width()).max();
+        let keys_width = keys_width.unwrap_or(0);
+        let width = bindings
+            .iter()
+            .map(|(_, action)| keys_width + 2 + action.width())
+            .max()
+            .unwrap_or(0);
+
+        // Leave room for the border and a column of padding either side.
+        let size = OffsetU16::new(
+            u16::try_from(width + 4).unwrap_or(u16::MAX),
+            u16::try_from(bindings.len() + 2).unwrap_or(u16::MAX),
+        )
+        .min(buffer.size());
+        let start = (buffer.size() - size) / 2;
+
+        let mut buffer = buffer.view_rect(Rect::from_size(start, size), false);
+        buffer.draw_box(Style::EMPTY);
+        buffer.draw_text([2, 0], " Help ", Style::EMPTY);
+
+        let mut inner = buffer.view(
+            2..size.x.saturating_sub(2),
+            1..size.y.saturating_sub(1),
+            false,
+        );
+        for (y, (keys, action)) in bindings.iter().enumerate() {
+            let Ok(y) = u16::try_from(y) else {
+                break;
+            };
+
+            inner.draw_text([0, y], keys, KEYS_STYLE);
+            inner.draw_text(
+                [u16::try_from(keys_width + 2).unwrap_or(u16::MAX), y],
+                action,
+                Style::EMPTY,
+            );
+        }
+    }
+
+    fn draw_status(&self, buffer: &mut BufferView) {
+        const STATUS_STYLE: Style = Style {
+            weight: Weight::Bold,
+            ..Style::EMPTY
+        };
+
+        const ERROR_STYLE: Style = Style {
+            fg: Color::Red,
+            ..Style::EMPTY
+        };
+
+        if matches!(self.mode, Mode::Command | Mode::Search) {
+            let prefix = match self.mode {
+                Mode::Command => ':',
+                _ => '/',
+            };
+
+            let text = format!("{prefix}{}", self.command);
+            buffer.draw_text(OffsetU16::ZERO, &text, Style::EMPTY);
+            buffer.set_cursor(Some(OffsetU16::new(text.width() as u16, 0)));
+            return;
+        }
+
+        let mut name = match self.document().path() {
+            Some(path) => path.display().to_string(),
+            None => "[No Name]".to_owned(),
+        };
+        if self.document().is_read_only() {
+            name.push_str(" [RO]");
+        }
+
+        match &self.message {
+            Some((message, kind)) => {
+                let style = match kind {
+                    MessageKind::Info => Style::EMPTY,
+                    MessageKind::Error => ERROR_STYLE,
+                };
+
+                buffer.draw_text(OffsetU16::ZERO, message, style);
+            }
+
+            None => {
+                let text = format!("{name} [{}/{}]", self.active + 1, self.documents.len());
+                buffer.draw_text(OffsetU16::ZERO, &text, STATUS_STYLE);
+            }
+        }
+
+        let (line, column) = self.document().cursor_line_col();
+        let position = format!("{}:{}", line + 1, column + 1);
+        let x = buffer.size().x.saturating_sub(position.width() as u16);
+        buffer.draw_text(OffsetU16::new(x, 0), &position, Style::EMPTY);
+    }
+
+    fn draw_panes(&mut self, buffer: &mut BufferView) {
+        const SEPARATOR_STYLE: Style = Style {
+            weight: Weight::Dim,
+            ..Style::EMPTY
+        };
+
+        // The first pane has focus, and always shows the active document.
+        self.panes[0].doc_index = self.active;
+
+        let num_panes = self.panes.len() as u16;
+        let pane_width = buffer.size().x.saturating_sub(num_panes - 1) / num_panes;
+
+        let mut rest = buffer.rect();
+
+        for i in 0..self.panes.len() {
+            let is_last = i + 1 == self.panes.len();
+            let focused = i == 0;
+
+            let rect = if is_last {
+                rest
+            } else {
+                let (rect, remaining) = rest.split_horizontal(pane_width);
+                let (separator, remaining) = remaining.split_horizontal(1);
+
+                for y in separator.start.y..separator.end.y {
+                    buffer.draw_text(OffsetU16::new(separator.start.x, y), "│", SEPARATOR_STYLE);
+                }
+
+                rest = remaining;
+                rect
+            };
+
+            self.panes[i].view_rect = rect;
+
+            self.draw_document(i, focused, &mut buffer.view_rect(rect, focused));
+        }
+    }
+
+    fn draw_document(&mut self, pane_index: usize, focused: bool, buffer: &mut BufferView) {
+        let doc_index = self.panes[pane_index].doc_index;
+
+        let gutter_width = if self.line_numbers {
+            gutters(&self.documents[doc_index]).max_width() as u16
+        } else {
+            0
+        };
+
+        let view_size: OffsetUsize = buffer
+            .size()
+            .saturating_sub(OffsetU16::new(gutter_width, 0))
+            .into();
+
+        if focused {
+            self.view_size = view_size;
+        }
+
+        let document = &mut self.documents[doc_index];
+        let pane = &mut self.panes[pane_index];
+
+        // Only the focused pane follows the cursor. The others keep their own
+        // scroll offset, which is clamped in case the text has shrunk.
+        if focused {
+            if self.wrap {
+                document.scroll_to_show_cursor_wrapped(view_size);
+            } else {
+                document.scroll_to_show_cursor(view_size, self.scrolloff);
+            }
+            pane.scroll_offset = document.scroll_offset();
+        } else {
+            pane.scroll_offset = document.clamp_scroll_offset(pane.scroll_offset, view_size);
+            if self.wrap {
+                pane.scroll_offset.x = 0;
+            }
+        }
+
+        let scroll_offset = pane.scroll_offset;
+        let document = &self.documents[doc_index];
+
+        let mut edit_view = buffer.view(gutter_width.., .., true);
+        let search_match = match focused {
+            true => self.search_match.clone(),
+            false => None,
+        };
+
+        let rows = self.draw_text(document, scroll_offset, search_match, &mut edit_view);
+
+        if focused {
+            self.draw_cursor(document, scroll_offset, &mut edit_view, &rows);
+        }
+
+        if self.line_numbers {
+            self.draw_gutter(document, scroll_offset.y, buffer, &rows);
+        }
+    }
+
+    fn draw_gutter(
+        &self,
+        document: &Document,
+        first_line: usize,
+        buffer: &mut BufferView,
+        rows: &[usize],
+    ) {
+        const GUTTER_STYLE: Style = Style {
+            weight: Weight::Dim,
+            ..Style::EMPTY
+        };
+
+        let height = buffer.size().y as usize;
+        let gutters = gutters(document);
+
+        if self.wrap {
+            // Mark the rows that continue a wrapped line.
+            let continuation = gutters.continuation();
+
+            for y in rows.windows(2).flat_map(|rows| rows[0] + 1..rows[1]) {
+                if y >= height {
+                    break;
+                }
+
+                buffer.draw_text(OffsetU16::new(0, y as u16), &continuation, GUTTER_STYLE);
+            }
+        }
+
+        for (gutter, &y) in gutters
+            .skip(first_line)
+            .zip(rows)
+            .take_while(|(_, &y)| y < height)
+        {
+            buffer.draw_text(OffsetU16::new(0, y as u16), &gutter, GUTTER_STYLE);
+        }
+    }
+
+    /// Draw the visible lines, returning the row each line starts on,
+    /// followed by the row after the last line.
+    fn draw_text(
+        &self,
+        document: &Document,
+        scroll_offset: OffsetUsize,
+        search_match: Option<Range<usize>>,
+        buffer: &mut BufferView,
+    ) -> Vec<usize> {
+        let size: OffsetUsize = buffer.size().into();
+
+        let selection = document.selection().unwrap_or_default();
+        let search_match = search_match.unwrap_or_default();
+
+        let mut rows = vec![];
+        let mut y = 0;
+
+        for (line_index, line) in document.visible_lines(scroll_offset, size) {
+            if y >= size.y {
+                break;
+            }
+
+            rows.push(y);
+
+            let line_start = document.rope().byte_of_line(line_index);
+            let line_end = line_start + line.byte_len();
+
+            // Only the diagnostics that overlap this line.
+            let diagnostics: Vec<_> = document
+                .diagnostics()
+                .iter()
+                .filter(|diagnostic| {
+                    diagnostic.range.start < line_end && diagnostic.range.end > line_start
+                })
+                .collect();
+
+            let highlight = |byte: usize| {
+                let byte = line_start + byte;
+
+                // Show the most severe diagnostic under the other highlights.
+                let diagnostic = diagnostics
+                    .iter()
+                    .filter(|diagnostic| diagnostic.range.contains(&byte))
+                    .map(|diagnostic| diagnostic.severity)
+                    .min()
+                    .map(diagnostic_style);
+                let base = diagnostic.unwrap_or(Style::EMPTY);
+
+                if search_match.contains(&byte) {
+                    Some(base.patch(SEARCH_MATCH_STYLE))
+                } else if selection.contains(&byte) {
+                    Some(base.patch(SELECTION_STYLE))
+                } else {
+                    diagnostic
+                }
+            };
+
+            let trailing_start = line.byte_len()
+                - line
+                    .chars()
+                    .rev()
+                    .take_while(|ch| matches!(ch, ' ' | '\t'))
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+
+            if self.wrap {
+                let mut line_rows = 1;
+
+                for grapheme in wrap_graphemes(line, size.x, document.tab_width()) {
+                    let pos = grapheme.pos + OffsetUsize::new(0, y);
+                    if pos.y >= size.y {
+                        break;
+                    }
+
+                    let cells = self.grapheme_cells(
+                        &grapheme.grapheme,
+                        grapheme.width,
+                        grapheme.byte >= trailing_start,
+                        highlight(grapheme.byte),
+                    );
+
+                    for (i, cell) in cells.enumerate().take(size.x.saturating_sub(pos.x)) {
+                        buffer[[(pos.x + i) as u16, pos.y as u16]] = Some(cell);
+                    }
+
+                    line_rows = grapheme.pos.y + 1;
+                }
+
+                y += line_rows;
+            } else {
+                let mut x = 0;
+                let mut byte = 0;
+
+                for grapheme in line.graphemes() {
+                    if x >= scroll_offset.x + size.x {
+                        break;
+                    }
+
+                    let width = grapheme_width(&grapheme, x, document.tab_width());
+                    let cells = self.grapheme_cells(
+                        &grapheme,
+                        width,
+                        byte >= trailing_start,
+                        highlight(byte),
+                    );
+
+                    for (i, cell) in cells.enumerate() {
+                        let Some(column) = (x + i).checked_sub(scroll_offset.x) else {
+                            continue;
+                        };
+
+                        if column >= size.x {
+                            break;
+                        }
+
+                        buffer[[column as u16, y as u16]] = Some(cell);
+                    }
+
+                    x += width;
+                    byte += grapheme.len();
+                }
+
+                y += 1;
+            }
+        }
+
+        rows.push(y);
+
+        if self.eof_markers {
+            // The empty line after a trailing newline is still part of the
+            // document.
+            let start = y + usize::from(document.rope().has_trailing_newline());
+            let marker = Cell::empty().with_char('~').with_style(EOF_MARKER_STYLE);
+
+            for row in start..size.y {
+                buffer[[0, row as u16]] = Some(marker.clone());
+            }
+        }
+
+        rows
+    }
+
+    /// The cells to draw for a grapheme that is `width` cells wide.
+    fn grapheme_cells(
+        &self,
+        grapheme: &str,
+        width: usize,
+        trailing: bool,
+        highlight: Option<Style>,
+    ) -> impl Iterator<Item = Cell> {
+        const WHITESPACE_STYLE: Style = Style {
+            weight: Weight::Dim,
+            ..Style::EMPTY
+        };
+
+        let (cell, fill) = match grapheme {
+            "\t" if self.render_whitespace => (
+                Cell::empty().with_char('→').with_style(WHITESPACE_STYLE),
+                width.saturating_sub(1),
+            ),
+            "\t" => (Cell::empty(), width.saturating_sub(1)),
+
+            " " if trailing && self.render_whitespace => {
+                (Cell::empty().with_char('·').with_style(WHITESPACE_STYLE), 0)
+            }
+
+            _ => (Cell::empty().with_grapheme(grapheme), 0),
+        };
+
+        std::iter::once(cell)
+            .chain(std::iter::repeat_n(Cell::empty(), fill))
+            .map(move |cell| match highlight {
+                Some(style) => {
+                    let style = cell.style().patch(style);
+                    cell.with_style(style)
+                }
+                None => cell,
+            })
+    }
+
+    fn draw_cursor(
+        &self,
+        document: &Document,
+        scroll_offset: OffsetUsize,
+        buffer: &mut BufferView,
+        rows: &[usize],
+    ) {
+        let cursor = if self.wrap {
+            let line = document.cursor_offset().y;
+            let offset = self
+                .document()
+                .cursor_offset_wrapped(buffer.size().x as usize);
+
+            line.checked_sub(scroll_offset.y)
+                .and_then(|i| rows.get(i))
+                .map(|&row| OffsetUsize::new(offset.x, row + offset.y))
+        } else {
+            // If we support cursors being offscreen, we can't use saturating sub.
+            Some(document.cursor_offset().saturating_sub(scroll_offset))
+        };
+
+        if let Some(cursor) = cursor {
+            if cursor.cmp_lt(buffer.size().into()).both() {
+                buffer.set_cursor(Some(OffsetU16::from(cursor)));
+            }
+        }
+
+        let style = self
+            .cursor_styles
+            .get(&self.mode)
+            .copied()
+            .unwrap_or_default();
+
+        buffer.set_cursor_style(style);
+    }
+}
+
+fn gutters(document: &Document) -> Gutters<'_> {
+    Gutters::new(document.rope(), "", "  ", "~", "↪")
+}
+
+struct Gutters<'a> {
+    lines: Range<usize>,
+    emit_blank: bool,
+
+    max_width: usize,
+
+    prefix: &'a str,
+    postfix: &'a str,
+    blank: &'a str,
+    continuation: &'a str,
+}
+
+impl<'a> Gutters<'a> {
+    fn new(
+        rope: &Rope,
+        prefix: &'a str,
+        postfix: &'a str,
+        blank: &'a str,
+        continuation: &'a str,
+    ) -> Self {
+        let len = rope.line_len();
+
+        let max_width = (len.checked_ilog10().unwrap_or_default() as usize + 1)
+            .max(blank.width())
+            .max(continuation.width());
+
+        Self {
+            lines: 0..len,
+            emit_blank: rope.has_trailing_newline(),
+
+            max_width,
+
+            prefix,
+            postfix,
+            blank,
+            continuation,
+        }
+    }
+
+    fn max_width(&self) -> usize {
+        self.max_width + self.prefix.width() + self.postfix.width()
+    }
+
+    /// The gutter for a row that continues a wrapped line.
+    fn continuation(&self) -> String {
+        format!(
+            "{}{:>w$}{}",
+            self.prefix,
+            self.continuation,
+            self.postfix,
+            w = self.max_width
+        )
+    }
+
+    fn next_with(&mut self, f: impl Fn(&mut Self) -> Option<usize>) -> Option<String> {
+        if let Some(line) = f(self) {
+            return Some(format!(
+                "{}{:>w$}{}",
+                self.prefix,
                 line + 1,
                 self.postfix,
                 w = self.max_width
@@ -232,3 +1489,1105 @@ impl Iterator for Gutters<'_> {
         self.next_with(|s| s.lines.nth(n))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ash_term::buffer::Buffer;
+    use ash_term::style::{Color, CursorShape, CursorStyle};
+    use ash_term::units::{OffsetU16, OffsetUsize, Rect};
+
+    use ash_term::event::{Event, KeyCode, KeyEvent, Modifiers};
+
+    use std::time::{Duration, Instant};
+
+    use super::{diagnostic_style, parse_command, Editor, MessageKind, Mode};
+    use crate::action::Action;
+    use crate::document::{Diagnostic, Document, IndentStyle, Motion, Severity};
+    use crate::state::StateStore;
+
+    fn editor(text: &str) -> Editor {
+        let mut document = Document::default();
+        document.insert_str(text);
+        Editor::new(document)
+    }
+
+    /// Draw the editor into a buffer of the given size, returning the text
+    /// of each row.
+    fn render(editor: &mut Editor, size: [u16; 2]) -> String {
+        let mut buffer = Buffer::new(size);
+        editor.draw(&mut buffer.view(true));
+        buffer.to_debug_string()
+    }
+
+    fn type_keys(editor: &mut Editor, keys: &str) {
+        for ch in keys.chars() {
+            let key = match ch {
+                '\r' => KeyCode::Return,
+                '\x1b' => KeyCode::Escape,
+                ch => KeyCode::Char(ch),
+            };
+            let _ = editor.handle_event(Event::key_no_mods(key));
+        }
+    }
+
+    fn grapheme(buffer: &mut Buffer, x: u16, y: u16) -> Option<String> {
+        buffer.view(false)[[x, y]]
+            .as_ref()
+            .map(|cell| cell.grapheme().to_owned())
+    }
+
+    #[test]
+    fn toggle_line_numbers() {
+        let mut editor = editor("hello");
+        let mut buffer = Buffer::new([20, 5]);
+
+        editor.draw(&mut buffer.view(true));
+        assert_eq!(grapheme(&mut buffer, 0, 0).as_deref(), Some("1"));
+        assert_eq!(grapheme(&mut buffer, 3, 0).as_deref(), Some("h"));
+
+        let _ = editor.handle_action(Action::ToggleLineNumbers);
+        assert!(!editor.line_numbers);
+        assert!(editor.take_force_redraw());
+
+        let mut buffer = Buffer::new([20, 5]);
+        editor.draw(&mut buffer.view(true));
+        assert_eq!(grapheme(&mut buffer, 0, 0).as_deref(), Some("h"));
+    }
+
+    #[test]
+    fn diagnostics() {
+        let mut editor = editor("let foo = 1;");
+        editor.line_numbers = false;
+        editor.documents[0].set_diagnostics(vec![
+            Diagnostic {
+                range: 4..7,
+                severity: Severity::Warning,
+            },
+            Diagnostic {
+                range: 6..9,
+                severity: Severity::Error,
+            },
+        ]);
+
+        let mut buffer = Buffer::new([20, 2]);
+        editor.draw(&mut buffer.view(true));
+
+        let mut style = |x: u16| buffer.view(false)[[x, 0]].as_ref().unwrap().style();
+
+        for x in [0, 3, 9] {
+            assert!(!style(x).underline);
+        }
+        for x in 4..6 {
+            assert_eq!(style(x), diagnostic_style(Severity::Warning));
+        }
+        // The error takes precedence where they overlap.
+        for x in 6..9 {
+            assert_eq!(style(x), diagnostic_style(Severity::Error));
+        }
+    }
+
+    #[test]
+    fn modify_number() {
+        let mut editor = editor("width: 9px");
+        let _ = editor.handle_action(Action::MoveHome);
+
+        let ctrl = |ch| Event::Key(KeyEvent::new_with_mods(KeyCode::Char(ch), Modifiers::CTRL));
+
+        let _ = editor.handle_event(ctrl('A'));
+        assert_eq!(editor.document().rope().to_string(), "width: 10px");
+        assert_eq!(editor.document().cursor_index(), 8);
+
+        let _ = editor.handle_event(ctrl('X'));
+        let _ = editor.handle_event(ctrl('X'));
+        assert_eq!(editor.document().rope().to_string(), "width: 8px");
+
+        // Nothing after the cursor.
+        type_keys(&mut editor, "l");
+        let _ = editor.handle_event(ctrl('A'));
+        assert_eq!(editor.document().rope().to_string(), "width: 8px");
+        assert!(editor.bell);
+    }
+
+    #[test]
+    fn eof_markers() {
+        let mut editor = editor("one\ntwo\nthree");
+        let _ = editor.handle_action(Action::MoveHome);
+        let mut buffer = Buffer::new([10, 10]);
+        editor.draw(&mut buffer.view(true));
+
+        // The body starts after the line number gutter.
+        let body = |buffer: &mut Buffer, y| grapheme(buffer, 3, y);
+
+        assert_eq!(body(&mut buffer, 2).as_deref(), Some("t"));
+        for y in 3..9 {
+            assert_eq!(body(&mut buffer, y).as_deref(), Some("~"));
+        }
+
+        // Not on the status line.
+        assert_ne!(body(&mut buffer, 9).as_deref(), Some("~"));
+
+        editor.eof_markers = false;
+        let mut buffer = Buffer::new([10, 10]);
+        editor.draw(&mut buffer.view(true));
+        assert_eq!(body(&mut buffer, 3), None);
+    }
+
+    #[test]
+    fn eof_markers_trailing_newline() {
+        let mut editor = editor("one\n");
+        let mut buffer = Buffer::new([10, 4]);
+        editor.draw(&mut buffer.view(true));
+
+        assert_eq!(grapheme(&mut buffer, 0, 1).as_deref(), Some("~"));
+        assert_eq!(grapheme(&mut buffer, 3, 1), None);
+        assert_eq!(grapheme(&mut buffer, 3, 2).as_deref(), Some("~"));
+    }
+
+    #[test]
+    fn render_whitespace() {
+        let mut editor = editor("\tab  ");
+        let _ = editor.handle_action(Action::ToggleLineNumbers);
+
+        let row = |editor: &mut Editor| {
+            let mut buffer = Buffer::new([12, 2]);
+            editor.draw(&mut buffer.view(true));
+            (0..12)
+                .map(|x| grapheme(&mut buffer, x, 0).unwrap_or_default())
+                .collect::<String>()
+        };
+
+        assert_eq!(row(&mut editor), "    ab  ");
+
+        editor.render_whitespace = true;
+        assert_eq!(row(&mut editor), "→   ab··");
+    }
+
+    #[test]
+    fn select_word() {
+        let mut editor = editor("let foo = 3;");
+        let _ = editor.handle_action(Action::MoveHome);
+        for _ in 0..5 {
+            let _ = editor.handle_action(Action::MoveRight);
+        }
+
+        let _ = editor.handle_action(Action::SelectWord);
+        assert_eq!(editor.document().selection(), Some(4..7));
+
+        let mut buffer = Buffer::new([20, 2]);
+        editor.draw(&mut buffer.view(true));
+        let mut bg = |x: u16| buffer.view(false)[[x, 0]].as_ref().unwrap().style().bg;
+        assert_eq!(bg(6), Color::Default);
+        assert_eq!(bg(7), Color::Blue);
+        assert_eq!(bg(9), Color::Blue);
+        assert_eq!(bg(10), Color::Default);
+
+        let _ = editor.handle_action(Action::ClearSelection);
+        assert_eq!(editor.document().selection(), None);
+    }
+
+    #[test]
+    fn buffer_list() {
+        let mut editor = editor("first");
+        editor.add_document(Document::default());
+        assert_eq!(editor.active, 0);
+
+        let _ = editor.handle_action(Action::NextBuffer);
+        assert_eq!(editor.active, 1);
+
+        let _ = editor.handle_action(Action::InsertChar('x'));
+        assert_eq!(editor.documents[0].rope().to_string(), "first");
+        assert_eq!(editor.documents[1].rope().to_string(), "x");
+
+        let _ = editor.handle_action(Action::NextBuffer);
+        assert_eq!(editor.active, 0);
+
+        let _ = editor.handle_action(Action::PrevBuffer);
+        assert_eq!(editor.active, 1);
+
+        let mut buffer = Buffer::new([20, 2]);
+        editor.draw(&mut buffer.view(true));
+        let status: String = (0..15)
+            .map(|x| grapheme(&mut buffer, x, 1).unwrap_or_default())
+            .collect();
+        assert_eq!(status, "[No Name] [2/2]");
+
+        let position: String = (17..20)
+            .map(|x| grapheme(&mut buffer, x, 1).unwrap_or_default())
+            .collect();
+        assert_eq!(position, "1:2");
+    }
+
+    #[test]
+    fn go_to_line() {
+        let mut editor = editor("one\ntwo\nthree");
+
+        type_keys(&mut editor, ":2\r");
+
+        assert_eq!(editor.document().cursor_line_col(), (1, 0));
+    }
+
+    #[test]
+    fn buffer_commands() {
+        let mut editor = editor("first");
+        editor.add_document(Document::default());
+
+        type_keys(&mut editor, ":bn\r");
+
+        assert_eq!(editor.active, 1);
+        assert_eq!(editor.mode, Mode::Normal);
+    }
+
+    #[test]
+    fn incremental_search() {
+        let mut editor = editor("one two three two");
+        let _ = editor.handle_action(Action::MoveHome);
+        let _ = editor.handle_action(Action::MoveRight);
+
+        type_keys(&mut editor, "/t");
+        assert_eq!(editor.mode, Mode::Search);
+        assert_eq!(editor.search_match, Some(4..5));
+        assert_eq!(editor.document().cursor_index(), 4);
+
+        type_keys(&mut editor, "h");
+        assert_eq!(editor.search_match, Some(8..10));
+        assert_eq!(editor.document().cursor_index(), 8);
+
+        let mut buffer = Buffer::new([30, 2]);
+        editor.draw(&mut buffer.view(true));
+        let bg = |buffer: &mut Buffer, x| buffer.view(false)[[x, 0]].as_ref().unwrap().style().bg;
+        assert_eq!(bg(&mut buffer, 3 + 8), Color::Yellow);
+        assert_eq!(bg(&mut buffer, 3 + 10), Color::Default);
+
+        // Cancelling restores the cursor.
+        type_keys(&mut editor, "\x1b");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.search_match, None);
+        assert_eq!(editor.document().cursor_index(), 1);
+    }
+
+    #[test]
+    fn search_commit() {
+        let mut editor = editor("one two three two");
+        let _ = editor.handle_action(Action::MoveHome);
+
+        type_keys(&mut editor, "/two\r");
+        assert_eq!(editor.mode, Mode::Normal);
+        assert_eq!(editor.document().cursor_index(), 4);
+
+        type_keys(&mut editor, "n");
+        assert_eq!(editor.document().cursor_index(), 14);
+    }
+
+    #[test]
+    fn message() {
+        let mut editor = editor("text");
+        editor.set_message("bad thing", MessageKind::Error);
+
+        let mut buffer = Buffer::new([20, 2]);
+        editor.draw(&mut buffer.view(true));
+
+        let status: String = (0..9)
+            .map(|x| grapheme(&mut buffer, x, 1).unwrap_or_default())
+            .collect();
+        assert_eq!(status, "bad thing");
+
+        let fg = buffer.view(false)[[0, 1]].as_ref().unwrap().style().fg;
+        assert_eq!(fg, Color::Red);
+
+        type_keys(&mut editor, "l");
+        assert_eq!(editor.message, None);
+    }
+
+    #[test]
+    fn unknown_command() {
+        let mut editor = editor("text");
+        type_keys(&mut editor, ":nope\r");
+        assert_eq!(
+            editor.message,
+            Some(("unknown command: nope".to_owned(), MessageKind::Error))
+        );
+    }
+
+    #[test]
+    fn stats_command() {
+        let mut editor = editor("one two\nthree");
+        type_keys(&mut editor, ":stats\r");
+        assert_eq!(
+            editor.message,
+            Some((
+                "2 lines, 3 words, 13 chars, 13 bytes".to_owned(),
+                MessageKind::Info
+            ))
+        );
+    }
+
+    #[test]
+    fn quit_modified() {
+        let mut editor = editor("text");
+
+        assert!(editor.handle_action(Action::Quit).is_continue());
+        assert_eq!(editor.message.as_ref().unwrap().1, MessageKind::Error);
+
+        assert!(editor.handle_action(Action::ForceQuit).is_break());
+    }
+
+    #[test]
+    fn bell() {
+        let mut editor = editor("text");
+
+        editor.bell();
+        assert!(editor.bell);
+
+        let mut buffer = Buffer::new([10, 2]);
+        editor.draw(&mut buffer.view(true));
+        assert!(!editor.bell);
+
+        let bg = |buffer: &mut Buffer| buffer.view(false)[[9, 1]].as_ref().unwrap().style().bg;
+        assert_eq!(bg(&mut buffer), Color::White);
+
+        // Only shown for one frame.
+        editor.draw(&mut buffer.view(true));
+        assert_ne!(
+            buffer.view(false)[[9, 1]]
+                .as_ref()
+                .map(|cell| cell.style().bg),
+            Some(Color::White)
+        );
+    }
+
+    #[test]
+    fn bell_on_invalid_action() {
+        let mut editor = editor("text");
+
+        let _ = editor.handle_action(Action::MoveRight);
+        assert!(editor.bell);
+        editor.bell = false;
+
+        type_keys(&mut editor, "Z");
+        assert!(editor.bell);
+        editor.bell = false;
+
+        // A prefix of a binding isn't invalid yet.
+        type_keys(&mut editor, "z");
+        assert!(!editor.bell);
+
+        // Nor are keys that are disabled on purpose.
+        editor.pending_keys.clear();
+        type_keys(&mut editor, ":");
+        let _ = editor.handle_event(Event::key_no_mods(KeyCode::Left));
+        assert!(!editor.bell);
+    }
+
+    #[test]
+    fn motions() {
+        let mut editor = editor("one two\nthree four\nfive");
+
+        let mut go = |motion| {
+            let _ = editor.handle_action(Action::Move(motion));
+            editor.document().cursor_line_col()
+        };
+
+        assert_eq!(go(Motion::DocumentStart), (0, 0));
+        assert_eq!(go(Motion::WordRight), (0, 4));
+        assert_eq!(go(Motion::LineDown), (1, 4));
+        assert_eq!(go(Motion::LineEnd), (1, 10));
+        assert_eq!(go(Motion::WordLeft), (1, 6));
+        assert_eq!(go(Motion::CharLeft), (1, 5));
+        assert_eq!(go(Motion::LineStart), (1, 0));
+        assert_eq!(go(Motion::DocumentEnd), (2, 4));
+    }
+
+    #[test]
+    fn smart_home() {
+        let mut editor = editor("    indented");
+        let mut home = || {
+            let _ = editor.handle_event(Event::key_no_mods(KeyCode::Home));
+            editor.document().cursor_line_col()
+        };
+
+        assert_eq!(home(), (0, 4));
+        assert_eq!(home(), (0, 0));
+        assert_eq!(home(), (0, 4));
+        assert!(!editor.bell);
+    }
+
+    #[test]
+    fn autosave() {
+        let path =
+            std::env::temp_dir().join(format!("ash_editor_test_autosave_{}", std::process::id()));
+        std::fs::write(&path, "text").unwrap();
+
+        let mut editor = Editor::new(Document::new(Some(path.clone())).unwrap());
+        let interval = Duration::from_secs(10);
+        let _ = editor.handle_action(Action::SetAutosave(Some(interval)));
+
+        // No edits, so nothing to save.
+        type_keys(&mut editor, "h");
+        assert!(editor.tick(Instant::now() + interval).is_none());
+
+        type_keys(&mut editor, "ix");
+        let now = Instant::now();
+        assert!(editor.tick(now).is_none());
+        assert!(editor.tick(now + interval / 2).is_none());
+
+        let action = editor.tick(now + interval);
+        assert!(matches!(action, Some(Action::AutoSave)));
+        let _ = editor.handle_action(action.unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), b"texxt");
+        assert!(!editor.document().is_modified());
+
+        // Only once per idle period.
+        assert!(editor.tick(now + interval * 2).is_none());
+
+        type_keys(&mut editor, "y");
+        assert!(matches!(
+            editor.tick(Instant::now() + interval),
+            Some(Action::AutoSave)
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn autosave_on_focus_lost() {
+        let path = std::env::temp_dir().join(format!(
+            "ash_editor_test_autosave_focus_{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, "text").unwrap();
+
+        let mut editor = Editor::new(Document::new(Some(path.clone())).unwrap());
+        type_keys(&mut editor, "ix");
+
+        // Autosave is off by default.
+        let _ = editor.handle_event(Event::FocusLost);
+        assert!(editor.document().is_modified());
+
+        let _ = editor.handle_action(Action::SetAutosave(Some(Duration::from_secs(10))));
+        let _ = editor.handle_event(Event::FocusGained);
+        assert!(editor.document().is_modified());
+
+        let _ = editor.handle_event(Event::FocusLost);
+        assert!(!editor.document().is_modified());
+        assert_eq!(std::fs::read(&path).unwrap(), b"textx");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn dirty() {
+        let mut editor = editor("text");
+        assert!(editor.is_dirty());
+        editor.clear_dirty();
+
+        let _ = editor.handle_event(Event::FocusGained);
+        assert!(!editor.is_dirty());
+
+        // Nor do an unbound key or a motion that goes nowhere, unless there's
+        // a visual bell to show.
+        editor.visual_bell = false;
+        type_keys(&mut editor, "Zl");
+        assert!(!editor.is_dirty());
+
+        editor.visual_bell = true;
+        type_keys(&mut editor, "Z");
+        assert!(editor.is_dirty());
+        editor.clear_dirty();
+
+        type_keys(&mut editor, "ix");
+        assert!(editor.is_dirty());
+        editor.clear_dirty();
+
+        // The bell is drawn for a single frame, then cleared.
+        editor.bell();
+        let mut buffer = Buffer::new([10, 2]);
+        editor.draw(&mut buffer.view(true));
+        assert!(editor.is_dirty());
+    }
+
+    #[test]
+    fn reload() {
+        let path =
+            std::env::temp_dir().join(format!("ash_editor_test_reload_{}", std::process::id()));
+        std::fs::write(&path, "text").unwrap();
+
+        let mut editor = Editor::new(Document::new(Some(path.clone())).unwrap());
+        std::fs::write(&path, "new text").unwrap();
+
+        type_keys(&mut editor, ":e\r");
+        assert_eq!(editor.document().rope().to_string(), "new text");
+        assert_eq!(editor.message.as_ref().unwrap().1, MessageKind::Info);
+
+        // Unsaved changes aren't discarded unless forced.
+        type_keys(&mut editor, "ix\x1b:e\r");
+        assert_eq!(editor.document().rope().to_string(), "new xtext");
+        assert_eq!(editor.message.as_ref().unwrap().1, MessageKind::Error);
+
+        type_keys(&mut editor, ":e!\r");
+        assert_eq!(editor.document().rope().to_string(), "new text");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn autosave_disabled() {
+        let mut editor = editor("text");
+        type_keys(&mut editor, "ix");
+        assert!(editor
+            .tick(Instant::now() + Duration::from_secs(3600))
+            .is_none());
+
+        type_keys(&mut editor, "\x1b:autosave 5\r");
+        assert_eq!(editor.autosave_interval, Some(Duration::from_secs(5)));
+
+        type_keys(&mut editor, ":autosave off\r");
+        assert_eq!(editor.autosave_interval, None);
+    }
+
+    #[test]
+    fn virtual_edit() {
+        let mut editor = editor("ab\nc");
+        let _ = editor.handle_action(Action::GoToLine(0));
+
+        // Moving within virtual space changes the cursor's offset, not its
+        // index, but isn't an error.
+        type_keys(&mut editor, "\\vllll");
+        assert!(!editor.bell);
+        assert_eq!(editor.document().cursor_offset(), OffsetUsize::new(4, 0));
+
+        type_keys(&mut editor, "ix");
+        assert_eq!(editor.document().rope().to_string(), "ab  x\nc");
+    }
+
+    #[test]
+    fn auto_pairs() {
+        let mut editor = editor("");
+        type_keys(&mut editor, "if(x)");
+        assert_eq!(editor.document().rope().to_string(), "f(x)");
+
+        type_keys(&mut editor, "\x1b\\pGi(");
+        assert_eq!(editor.document().rope().to_string(), "f(x)(");
+    }
+
+    #[test]
+    fn auto_pairs_backspace() {
+        let mut editor = editor("");
+        let backspace = Event::key_no_mods(KeyCode::Backspace);
+
+        type_keys(&mut editor, "i[(");
+        assert_eq!(editor.document().rope().to_string(), "[()]");
+
+        let _ = editor.handle_event(backspace.clone());
+        assert_eq!(editor.document().rope().to_string(), "[]");
+
+        // Without auto pairs, only the opener is deleted.
+        type_keys(&mut editor, "\x1b\\pi");
+        let _ = editor.handle_event(backspace);
+        assert_eq!(editor.document().rope().to_string(), "]");
+    }
+
+    #[test]
+    fn repeat_insert() {
+        let mut editor = editor("one\ntwo");
+        type_keys(&mut editor, "ifoo\x1bk.");
+        assert_eq!(editor.document().rope().to_string(), "onefoo\ntwofoo");
+
+        // Leaving insert mode without typing doesn't replace the last change.
+        type_keys(&mut editor, "i\x1bjh.");
+        assert_eq!(editor.document().rope().to_string(), "onefoo\ntwofofooo");
+    }
+
+    #[test]
+    fn repeat_normal_edit() {
+        let mut editor = editor("abcdef");
+        type_keys(&mut editor, "hhhxh.");
+        assert_eq!(editor.document().rope().to_string(), "abef");
+
+        // Motions aren't changes.
+        type_keys(&mut editor, "h.");
+        assert_eq!(editor.document().rope().to_string(), "aef");
+    }
+
+    #[test]
+    fn repeat_open_line() {
+        let mut editor = editor("one");
+        type_keys(&mut editor, "otwo\x1b.");
+        assert_eq!(editor.document().rope().to_string(), "one\ntwo\ntwo");
+    }
+
+    #[test]
+    fn macros() {
+        let mut editor = editor("one\ntwo\nthree");
+        type_keys(&mut editor, "ggqaxjq");
+        assert_eq!(editor.document().rope().to_string(), "ne\ntwo\nthree");
+        assert!(editor.recording.is_none());
+
+        type_keys(&mut editor, "@a");
+        assert_eq!(editor.document().rope().to_string(), "ne\nwo\nthree");
+        assert_eq!(editor.document().cursor_line_col(), (2, 0));
+    }
+
+    #[test]
+    fn macro_recursion() {
+        let mut editor = editor("abcdef");
+        type_keys(&mut editor, "ggqbx@bq");
+        assert_eq!(editor.document().rope().to_string(), "bcdef");
+
+        // The macro deletes once, then refuses to play itself.
+        editor.bell = false;
+        type_keys(&mut editor, "@b");
+        assert_eq!(editor.document().rope().to_string(), "cdef");
+        assert!(editor.bell);
+    }
+
+    #[test]
+    fn wrap_continuation_marker() {
+        let mut editor = editor("0123456789\nab");
+        let _ = editor.handle_action(Action::ToggleWrap);
+
+        let mut buffer = Buffer::new([8, 5]);
+        editor.draw(&mut buffer.view(true));
+
+        assert_eq!(grapheme(&mut buffer, 0, 0).as_deref(), Some("1"));
+        assert_eq!(grapheme(&mut buffer, 3, 1).as_deref(), Some("5"));
+        assert_eq!(grapheme(&mut buffer, 0, 1).as_deref(), Some("↪"));
+        assert_eq!(grapheme(&mut buffer, 0, 2).as_deref(), Some("2"));
+        assert_ne!(grapheme(&mut buffer, 0, 3).as_deref(), Some("↪"));
+    }
+
+    #[test]
+    fn marks() {
+        let mut editor = editor("one\ntwo");
+        type_keys(&mut editor, "ggmxGiend\x1b`x");
+        assert_eq!(editor.document().rope().to_string(), "one\ntwoend");
+        assert_eq!(editor.document().cursor_index(), 0);
+
+        editor.bell = false;
+        type_keys(&mut editor, "`y");
+        assert!(editor.bell);
+    }
+
+    #[test]
+    fn cursor_styles() {
+        let mut editor = editor("text");
+
+        let style = |editor: &mut Editor| {
+            let mut buffer = Buffer::new([10, 3]);
+            editor.draw(&mut buffer.view(true));
+            buffer.cursor_style
+        };
+
+        assert_eq!(style(&mut editor).shape, CursorShape::Block);
+
+        let underline = CursorStyle {
+            shape: CursorShape::Underscore,
+            blinking: false,
+        };
+        editor.cursor_styles.insert(Mode::Insert, underline);
+
+        type_keys(&mut editor, "i");
+        assert_eq!(style(&mut editor), underline);
+    }
+
+    #[test]
+    fn substitute_command() {
+        let mut editor = editor("a-b-c");
+        type_keys(&mut editor, ":%s/-/ + /g\r");
+        assert_eq!(editor.document().rope().to_string(), "a + b + c");
+        assert_eq!(
+            editor.message,
+            Some(("2 substitutions".to_owned(), MessageKind::Info))
+        );
+
+        type_keys(&mut editor, ":%s#b#\r");
+        assert_eq!(editor.document().rope().to_string(), "a +  + c");
+
+        type_keys(&mut editor, ":%s/x/y\r");
+        assert_eq!(editor.message.unwrap().1, MessageKind::Error);
+    }
+
+    #[test]
+    fn help() {
+        let mut editor = editor("abc");
+        type_keys(&mut editor, ":help\r");
+
+        let rendered = render(&mut editor, [40, 200]);
+        assert!(rendered.contains("┌─ Help "));
+        assert!(rendered
+            .lines()
+            .any(|line| line.contains("│ i ") && line.contains("insert mode")));
+
+        // Any key dismisses the help, without doing anything else.
+        type_keys(&mut editor, "x");
+        assert!(!render(&mut editor, [40, 200]).contains("Help"));
+        assert_eq!(editor.document().rope().to_string(), "abc");
+    }
+
+    #[test]
+    fn set_tabstop() {
+        let mut editor = editor("\tx");
+
+        type_keys(&mut editor, ":set ts=2\r");
+        assert_eq!(editor.document().tab_width(), 2);
+        assert_eq!(editor.document().cursor_offset(), OffsetUsize::new(3, 0));
+
+        type_keys(&mut editor, ":set tabstop=0\r");
+        assert_eq!(editor.document().tab_width(), 2);
+        assert_eq!(
+            editor.message,
+            Some((
+                "unknown command: set tabstop=0".to_owned(),
+                MessageKind::Error
+            ))
+        );
+    }
+
+    #[test]
+    fn indent_style() {
+        let mut editor = editor("");
+        let tab = Event::key_no_mods(KeyCode::Tab);
+
+        type_keys(&mut editor, "i");
+        let _ = editor.handle_event(tab.clone());
+        type_keys(&mut editor, "a\rb\x1b");
+        assert_eq!(editor.document().rope().to_string(), "    a\n    b");
+
+        type_keys(&mut editor, ":set noet\r");
+        assert_eq!(editor.document().indent_style(), IndentStyle::Tabs);
+
+        type_keys(&mut editor, "<<>>");
+        let _ = editor.handle_event(Event::key_no_mods(KeyCode::End));
+        type_keys(&mut editor, "i");
+        let _ = editor.handle_event(tab);
+        type_keys(&mut editor, "c");
+        assert_eq!(editor.document().rope().to_string(), "    a\n\tb\tc");
+
+        type_keys(&mut editor, "\x1b:set et\r");
+        assert_eq!(editor.document().indent_style(), IndentStyle::Spaces(4));
+
+        // Spaces already in use keep their width.
+        editor.documents[0].set_indent_style(IndentStyle::Spaces(2));
+        type_keys(&mut editor, ":set et\r");
+        assert_eq!(editor.document().indent_style(), IndentStyle::Spaces(2));
+    }
+
+    #[test]
+    fn tab_with_selection() {
+        let mut editor = editor("foo bar");
+        let tab = Event::key_no_mods(KeyCode::Tab);
+        let back_tab = Event::Key(KeyEvent::new_with_mods(KeyCode::Tab, Modifiers::SHIFT));
+
+        let _ = editor.handle_action(Action::MoveHome);
+        type_keys(&mut editor, "i");
+        let _ = editor.handle_event(tab.clone());
+        assert_eq!(editor.document().rope().to_string(), "    foo bar");
+
+        // With a selection, the line is indented rather than the selection
+        // replaced.
+        type_keys(&mut editor, "\x1bwviw");
+        let _ = editor.handle_event(tab);
+        assert_eq!(editor.document().rope().to_string(), "        foo bar");
+        assert!(editor.document().selection().is_some());
+
+        let _ = editor.handle_event(back_tab.clone());
+        let _ = editor.handle_event(back_tab.clone());
+        assert_eq!(editor.document().rope().to_string(), "foo bar");
+
+        let _ = editor.handle_event(back_tab);
+        assert!(editor.bell);
+    }
+
+    #[test]
+    fn read_only() {
+        let path =
+            std::env::temp_dir().join(format!("ash_editor_test_read_only_{}", std::process::id()));
+        std::fs::write(&path, b"ab\xff").unwrap();
+
+        let mut editor = Editor::new(Document::new(Some(path.clone())).unwrap());
+        assert!(editor.document().is_read_only());
+
+        let text = editor.document().rope().to_string();
+        type_keys(&mut editor, "ix\x1bdd");
+        assert_eq!(editor.document().rope().to_string(), text);
+        assert_eq!(
+            editor.message,
+            Some(("buffer is read-only".to_owned(), MessageKind::Error))
+        );
+
+        type_keys(&mut editor, ":w\r");
+        assert_eq!(
+            editor.message,
+            Some(("file is read-only".to_owned(), MessageKind::Error))
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), b"ab\xff");
+
+        // Making it writable would let the hex dump be saved over the file.
+        type_keys(&mut editor, ":set noro\r:w\r");
+        assert_eq!(
+            editor.message,
+            Some(("file is read-only".to_owned(), MessageKind::Error))
+        );
+        assert!(editor.document().is_read_only());
+        assert_eq!(std::fs::read(&path).unwrap(), b"ab\xff");
+
+        editor.message = None;
+        let status = render(&mut editor, [200, 2]);
+        assert!(status.contains("[RO]"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn set_fixeol() {
+        let path =
+            std::env::temp_dir().join(format!("ash_editor_test_fixeol_{}", std::process::id()));
+        std::fs::write(&path, "text").unwrap();
+
+        let mut editor = Editor::new(Document::new(Some(path.clone())).unwrap());
+        type_keys(&mut editor, ":w\r");
+        assert_eq!(std::fs::read(&path).unwrap(), b"text");
+
+        type_keys(&mut editor, ":set fixeol\r:w\r");
+        assert_eq!(std::fs::read(&path).unwrap(), b"text\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn set_number_and_wrap() {
+        assert!(matches!(
+            parse_command("set nu"),
+            Some(Action::SetLineNumbers(true))
+        ));
+        assert!(matches!(
+            parse_command("set nonumber"),
+            Some(Action::SetLineNumbers(false))
+        ));
+        assert!(matches!(
+            parse_command("set wrap"),
+            Some(Action::SetWrap(true))
+        ));
+        assert!(matches!(
+            parse_command("set nowrap"),
+            Some(Action::SetWrap(false))
+        ));
+
+        let mut editor = editor("text");
+        type_keys(&mut editor, ":set nonu\r");
+        assert!(!editor.line_numbers);
+        assert!(editor.take_force_redraw());
+
+        // Setting an option twice leaves it set, rather than toggling it.
+        type_keys(&mut editor, ":set wrap\r:set wrap\r");
+        assert!(editor.wrap);
+        type_keys(&mut editor, ":set number\r:set nowrap\r");
+        assert!(editor.line_numbers);
+        assert!(!editor.wrap);
+    }
+
+    #[test]
+    fn set_read_only() {
+        let mut editor = editor("abc");
+        type_keys(&mut editor, ":set ro\r");
+        assert!(editor.document().is_read_only());
+
+        let _ = editor.handle_action(Action::InsertChar('x'));
+        let _ = editor.handle_action(Action::Backspace);
+        let _ = editor.handle_action(Action::Combo(vec![Action::MoveHome, Action::Delete]));
+        assert_eq!(editor.document().rope().to_string(), "abc");
+        assert_eq!(
+            editor.message,
+            Some(("buffer is read-only".to_owned(), MessageKind::Error))
+        );
+
+        type_keys(&mut editor, ":set noro\r");
+        let _ = editor.handle_action(Action::InsertChar('x'));
+        assert_eq!(editor.document().rope().to_string(), "abcx");
+    }
+
+    #[test]
+    fn render_empty() {
+        let mut editor = editor("");
+
+        assert_eq!(
+            render(&mut editor, [24, 4]),
+            [
+                "~                       ",
+                "   ~                    ",
+                "   ~                    ",
+                "[No Name] [1/1]      1:1",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn render_scrolled_vertically() {
+        let text: String = (1..=20).map(|i| format!("line {i}\n")).collect();
+        let mut editor = editor(&text);
+        let _ = editor.handle_action(Action::GoToLine(9));
+
+        assert_eq!(
+            render(&mut editor, [24, 6]),
+            [
+                " 8  line 8              ",
+                " 9  line 9              ",
+                "10  line 10             ",
+                "11  line 11             ",
+                "12  line 12             ",
+                "[No Name] [1/1]     10:1",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn render_scrolled_horizontally() {
+        let mut editor = editor("short\nthis line is much too long to fit\nend");
+        let _ = editor.handle_action(Action::GoToLine(1));
+        let _ = editor.handle_action(Action::MoveEnd);
+
+        assert_eq!(
+            render(&mut editor, [24, 4]),
+            [
+                "1                       ",
+                "2  h too long to fit    ",
+                "3                       ",
+                "[No Name] [1/1]     2:34",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn split() {
+        let mut editor = editor("left");
+        let _ = editor.handle_action(Action::MoveHome);
+
+        let mut right = Document::default();
+        right.insert_str("right");
+        right.move_home();
+        editor.add_document(right);
+
+        let _ = editor.handle_action(Action::ToggleLineNumbers);
+        let _ = editor.handle_action(Action::Split);
+
+        let mut buffer = Buffer::new([12, 3]);
+        editor.draw(&mut buffer.view(true));
+
+        let text = buffer.to_debug_string();
+        let rows: Vec<_> = text.lines().take(2).collect();
+        assert_eq!(rows, ["left │right ", "~    │~     "]);
+
+        assert_eq!(
+            editor.panes[1].view_rect,
+            Rect::new(OffsetU16::new(6, 0), OffsetU16::new(12, 2))
+        );
+
+        let _ = editor.handle_action(Action::CloseSplits);
+        assert_eq!(editor.panes.len(), 1);
+    }
+
+    #[test]
+    fn split_scrolls_panes_separately() {
+        let text: String = (0..20).map(|i| format!("{i}\n")).collect();
+        let mut editor = editor(&text);
+        editor.scrolloff = 0;
+        let _ = editor.handle_action(Action::ToggleLineNumbers);
+        let _ = editor.handle_action(Action::Move(Motion::DocumentStart));
+        let _ = editor.handle_action(Action::Split);
+
+        let text = render(&mut editor, [7, 4]);
+        let rows: Vec<_> = text.lines().take(3).collect();
+        assert_eq!(rows, ["0  │0  ", "1  │1  ", "2  │2  "]);
+
+        // Only the focused pane follows the cursor to the end.
+        let _ = editor.handle_action(Action::Move(Motion::DocumentEnd));
+        let text = render(&mut editor, [7, 4]);
+        let rows: Vec<_> = text.lines().take(3).collect();
+        assert_eq!(rows, ["18 │0  ", "19 │1  ", "   │2  "]);
+        assert_eq!(editor.document().scroll_offset().y, 18);
+        assert_eq!(editor.panes[1].scroll_offset.y, 0);
+    }
+
+    #[test]
+    fn toggle_wrap() {
+        let mut editor = editor("0123456789");
+        let _ = editor.handle_action(Action::ToggleLineNumbers);
+
+        let mut buffer = Buffer::new([6, 5]);
+        editor.draw(&mut buffer.view(true));
+        assert_eq!(grapheme(&mut buffer, 0, 1).as_deref(), Some("~"));
+
+        let _ = editor.handle_action(Action::ToggleWrap);
+        assert!(editor.wrap);
+        assert!(editor.take_force_redraw());
+
+        let mut buffer = Buffer::new([6, 5]);
+        editor.draw(&mut buffer.view(true));
+        assert_eq!(grapheme(&mut buffer, 0, 0).as_deref(), Some("0"));
+        assert_eq!(grapheme(&mut buffer, 0, 1).as_deref(), Some("6"));
+        assert_eq!(buffer.cursor.map(|c| [c.x, c.y]), Some([4, 1]));
+    }
+
+    #[test]
+    fn restore_state() {
+        let dir =
+            std::env::temp_dir().join(format!("ash_editor_test_state_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("file.txt");
+        let store_path = dir.join("state");
+        std::fs::write(&file, "one\ntwo\nthree\nfour\n").unwrap();
+
+        let mut editor = Editor::new(Document::new(Some(file.clone())).unwrap());
+        editor.documents[0].set_cursor_index(5);
+        editor.documents[0].set_scroll_offset(OffsetUsize::new(0, 2), OffsetUsize::new(10, 1));
+
+        let mut store = StateStore::load(store_path.clone()).unwrap();
+        editor.save_state(&mut store);
+        store.save().unwrap();
+
+        let store = StateStore::load(store_path.clone()).unwrap();
+        let mut editor = Editor::new(Document::new(Some(file.clone())).unwrap());
+        editor.load_state(&store);
+        assert_eq!(editor.document().cursor_index(), 5);
+        assert_eq!(editor.document().scroll_offset(), OffsetUsize::new(0, 2));
+
+        // The file has shrunk since, so the position is clamped.
+        std::fs::write(&file, "on").unwrap();
+        let mut editor = Editor::new(Document::new(Some(file)).unwrap());
+        editor.load_state(&store);
+        assert_eq!(editor.document().cursor_index(), 2);
+        assert_eq!(editor.document().scroll_offset(), OffsetUsize::new(0, 0));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn scrolled_rows() {
+        let text: String = (0..100).map(|i| format!("{i}\n")).collect();
+        let mut editor = editor(&text);
+        editor.documents[0].set_cursor_index(0);
+
+        render(&mut editor, [20, 10]);
+        assert_eq!(editor.scrolled_rows(), None);
+
+        for _ in 0..6 {
+            editor.documents[0].move_down();
+        }
+        render(&mut editor, [20, 10]);
+        assert_eq!(editor.document().scroll_offset().y, 1);
+        assert_eq!(editor.scrolled_rows(), Some((0..9, 1)));
+
+        render(&mut editor, [20, 10]);
+        assert_eq!(editor.scrolled_rows(), None);
+
+        // With more than one pane, the rows don't move together.
+        let _ = editor.handle_action(Action::Split);
+        render(&mut editor, [20, 10]);
+        editor.documents[0].set_cursor_index(0);
+        render(&mut editor, [20, 10]);
+        assert_eq!(editor.document().scroll_offset().y, 0);
+        assert_eq!(editor.scrolled_rows(), None);
+    }
+}
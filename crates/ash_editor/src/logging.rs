@@ -0,0 +1,118 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use log::LevelFilter;
+
+/// Where log messages go.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogTarget {
+    #[allow(dead_code, reason = "stderr is drawn over while the editor runs")]
+    Stderr,
+    File(PathBuf),
+    #[allow(dead_code, reason = "stderr is drawn over while the editor runs")]
+    Both(PathBuf),
+}
+
+/// Set up the global logger.
+pub fn init(level: LevelFilter, target: LogTarget) -> Result<()> {
+    dispatch(level, target)?.apply()?;
+    Ok(())
+}
+
+fn dispatch(level: LevelFilter, target: LogTarget) -> Result<fern::Dispatch> {
+    let mut dispatch = fern::Dispatch::new()
+        .format(|out, message, record| {
+            let now = chrono::Local::now();
+
+            out.finish(format_args!(
+                "[{} {} {}] {}",
+                now.format("%Y/%m/%d %H:%M:%S"),
+                record.level(),
+                record.target(),
+                message
+            ))
+        })
+        .level(level);
+
+    if let LogTarget::Stderr | LogTarget::Both(_) = target {
+        dispatch = dispatch.chain(std::io::stderr());
+    }
+
+    if let LogTarget::File(path) | LogTarget::Both(path) = target {
+        dispatch = dispatch.chain(fern::log_file(path)?);
+    }
+
+    Ok(dispatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, LevelFilter, Record};
+
+    use super::{dispatch, LogTarget};
+
+    #[test]
+    fn file_target() {
+        let path = std::env::temp_dir().join(format!("ash_editor_test_log_{}", std::process::id()));
+
+        let (_, logger) = dispatch(LevelFilter::Info, LogTarget::File(path.clone()))
+            .unwrap()
+            .into_log();
+        assert!(path.exists());
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("hello"))
+                .level(Level::Info)
+                .target("test")
+                .build(),
+        );
+        logger.log(
+            &Record::builder()
+                .args(format_args!("too verbose"))
+                .level(Level::Debug)
+                .target("test")
+                .build(),
+        );
+        logger.flush();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.ends_with(" INFO test] hello\n"), "{contents:?}");
+        assert!(!contents.contains("too verbose"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn both_target() {
+        let path =
+            std::env::temp_dir().join(format!("ash_editor_test_log_both_{}", std::process::id()));
+
+        let (_, logger) = dispatch(LevelFilter::Info, LogTarget::Both(path.clone()))
+            .unwrap()
+            .into_log();
+        assert!(path.exists());
+
+        logger.log(
+            &Record::builder()
+                .args(format_args!("hello"))
+                .level(Level::Info)
+                .target("test")
+                .build(),
+        );
+        logger.flush();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(contents.ends_with(" INFO test] hello\n"), "{contents:?}");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn stderr_target() {
+        let (level, _) = dispatch(LevelFilter::Warn, LogTarget::Stderr)
+            .unwrap()
+            .into_log();
+        assert_eq!(level, LevelFilter::Warn);
+    }
+}
@@ -1,24 +1,34 @@
 mod action;
 mod document;
 mod editor;
+mod logging;
 mod panic;
+mod state;
+#[allow(dead_code)]
 mod utils;
 
+use std::io::{self, IsTerminal};
 use std::ops::ControlFlow;
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use ash_term::buffer::Buffer;
-use ash_term::draw_buffer::draw_diff;
+use ash_term::draw_buffer::{draw_diff, draw_scroll};
 use ash_term::platform::{Events, PlatformTerminal, Terminal, Writer};
 use ash_term::units::OffsetU16;
 use clap::Parser;
 use document::Document;
 use editor::Editor;
+use logging::LogTarget;
+use state::StateStore;
 
 const FRAME_RATE: Duration = Duration::from_millis(17);
 
+/// How often to wake up when nothing has changed. The terminal doesn't notify
+/// us of resizes, so it still has to be polled for its size.
+const IDLE_POLL_RATE: Duration = Duration::from_millis(250);
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Mode {
     #[default]
@@ -28,11 +38,19 @@ pub enum Mode {
 
 #[derive(Parser)]
 struct Args {
-    path: Option<PathBuf>,
+    paths: Vec<PathBuf>,
+
+    /// Scroll the text with the terminal's scroll regions, rather than
+    /// redrawing it.
+    #[arg(long)]
+    fast_scroll: bool,
 }
 
 fn main() -> Result<()> {
-    init_logging()?;
+    logging::init(
+        log::LevelFilter::Debug,
+        LogTarget::File("logs/editor.log".into()),
+    )?;
 
     let args = Args::parse();
 
@@ -41,26 +59,6 @@ fn main() -> Result<()> {
     Ok(())
 }
 
-fn init_logging() -> Result<()> {
-    fern::Dispatch::new()
-        .format(|out, message, record| {
-            let now = chrono::Local::now();
-
-            out.finish(format_args!(
-                "[{} {} {}] {}",
-                now.format("%Y/%m/%d %H:%M:%S"),
-                record.level(),
-                record.target(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Debug)
-        .chain(fern::log_file("logs/editor.log")?)
-        .apply()?;
-
-    Ok(())
-}
-
 struct App {
     terminal: PlatformTerminal,
 
@@ -68,11 +66,38 @@ struct App {
     char_buf: Buffer,
 
     editor: Editor,
+    /// Where files were left in previous sessions, if it could be loaded.
+    state: Option<StateStore>,
+
+    fast_scroll: bool,
 }
 
 impl App {
     fn new(args: Args) -> Result<Self> {
-        let document = Document::new(args.path)?;
+        let mut paths = args.paths.into_iter();
+
+        // Text piped in is opened as an unnamed document. It has to be read
+        // before the terminal is set up, which takes over stdin.
+        let first = match paths.next() {
+            None if !io::stdin().is_terminal() => {
+                Document::from_reader(io::stdin().lock()).context("couldn't read stdin")?
+            }
+            path => Document::new(path)?,
+        };
+
+        let mut editor = Editor::new(first);
+        for path in paths {
+            editor.add_document(Document::new(Some(path))?);
+        }
+
+        let state = StateStore::default_path().and_then(|path| {
+            StateStore::load(path)
+                .inspect_err(|err| log::error!("{err:#}"))
+                .ok()
+        });
+        if let Some(state) = &state {
+            editor.load_state(state);
+        }
 
         Ok(Self {
             terminal: PlatformTerminal::init()?,
@@ -80,15 +105,34 @@ impl App {
             char_buf_prev: Buffer::new(OffsetU16::ZERO),
             char_buf: Buffer::new(OffsetU16::ZERO),
 
-            editor: Editor::new(document),
+            editor,
+            state,
+            fast_scroll: args.fast_scroll,
         })
     }
 
     fn run(mut self) -> Result<()> {
+        let res = self.event_loop();
+
+        if let Some(state) = &mut self.state {
+            self.editor.save_state(state);
+            if let Err(err) = state.save() {
+                log::error!("{err:#}");
+            }
+        }
+
+        res
+    }
+
+    fn event_loop(&mut self) -> Result<()> {
         self.draw()?;
 
         loop {
-            let deadline = Instant::now() + FRAME_RATE;
+            let timeout = match self.editor.is_dirty() {
+                true => FRAME_RATE,
+                false => IDLE_POLL_RATE,
+            };
+            let deadline = Instant::now() + timeout;
 
             if let Some(event) = self.terminal.events().read_with_deadline(deadline)? {
                 log::debug!("event: {event:?}");
@@ -98,16 +142,36 @@ impl App {
                 }
             }
 
-            self.draw()?;
+            if let Some(action) = self.editor.tick(Instant::now()) {
+                if let ControlFlow::Break(res) = self.editor.handle_action(action) {
+                    return res;
+                }
+            }
+
+            let size = self.terminal.size()?;
+            if self.editor.is_dirty() || size != self.char_buf.size() {
+                self.draw()?;
+            }
         }
     }
 
     fn draw(&mut self) -> Result<()> {
         let size = self.terminal.size()?;
+        self.editor.clear_dirty();
+
+        if self.editor.take_force_redraw() {
+            self.char_buf_prev = Buffer::new(OffsetU16::ZERO);
+        }
 
         self.char_buf.resize_and_clear(size);
         self.editor.draw(&mut self.char_buf.view(true));
 
+        if self.fast_scroll && self.char_buf_prev.size() == size {
+            if let Some((rows, delta)) = self.editor.scrolled_rows() {
+                draw_scroll(&mut self.char_buf_prev, rows, delta, self.terminal.writer());
+            }
+        }
+
         draw_diff(
             &self.char_buf_prev.view(false),
             &self.char_buf.view(false),
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use ash_term::units::OffsetUsize;
+
+/// Where a file was left, to restore when it's next opened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileState {
+    /// Cursor position, as a byte index.
+    pub cursor_index: usize,
+    pub scroll_offset: OffsetUsize,
+}
+
+/// The state of each file that's been open, kept between sessions.
+///
+/// It's stored as a line per file, holding the cursor index, the scroll
+/// offset, and last the absolute path, separated by spaces.
+#[derive(Debug, Default)]
+pub struct StateStore {
+    path: PathBuf,
+    files: HashMap<PathBuf, FileState>,
+}
+
+impl StateStore {
+    /// The default place to keep the state, in the user's data directory.
+    pub fn default_path() -> Option<PathBuf> {
+        let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+        };
+
+        Some(data_dir.join("ash_editor").join("state"))
+    }
+
+    /// Load the store kept at `path`. If there isn't one yet, it starts empty.
+    /// Lines that can't be parsed are skipped.
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).context("couldn't read editor state"),
+        };
+
+        let files = source.lines().filter_map(parse_line).collect();
+
+        Ok(Self { path, files })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).context("couldn't create state directory")?;
+        }
+
+        let mut out = String::new();
+        for (path, state) in &self.files {
+            // A newline in the path would break the format.
+            let Some(path) = path.to_str().filter(|path| !path.contains('\n')) else {
+                continue;
+            };
+
+            out.push_str(&format!(
+                "{} {} {} {path}\n",
+                state.cursor_index, state.scroll_offset.x, state.scroll_offset.y
+            ));
+        }
+
+        fs::write(&self.path, out).context("couldn't write editor state")
+    }
+
+    pub fn get(&self, path: &Path) -> Option<FileState> {
+        self.files.get(&absolute(path)).copied()
+    }
+
+    pub fn insert(&mut self, path: &Path, state: FileState) {
+        self.files.insert(absolute(path), state);
+    }
+}
+
+fn parse_line(line: &str) -> Option<(PathBuf, FileState)> {
+    let mut parts = line.splitn(4, ' ');
+    let mut number = || parts.next()?.parse::<usize>().ok();
+
+    let state = FileState {
+        cursor_index: number()?,
+        scroll_offset: OffsetUsize::new(number()?, number()?),
+    };
+    let path = parts.next().filter(|path| !path.is_empty())?;
+
+    Some((PathBuf::from(path), state))
+}
+
+/// Files are keyed by their absolute path, so they're found whatever
+/// directory the editor is started in.
+fn absolute(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use ash_term::units::OffsetUsize;
+
+    use super::{parse_line, FileState, StateStore};
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("ash_editor_state_{}", std::process::id()));
+        let path = dir.join("state");
+
+        let state = FileState {
+            cursor_index: 12,
+            scroll_offset: OffsetUsize::new(3, 4),
+        };
+
+        let mut store = StateStore::load(path.clone()).unwrap();
+        store.insert("some file.txt".as_ref(), state);
+        store.save().unwrap();
+
+        let store = StateStore::load(path).unwrap();
+        assert_eq!(store.get("some file.txt".as_ref()), Some(state));
+        assert_eq!(store.get("other.txt".as_ref()), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parse_bad_lines() {
+        assert!(parse_line("").is_none());
+        assert!(parse_line("1 2 3").is_none());
+        assert!(parse_line("1 2 x /a").is_none());
+        assert_eq!(
+            parse_line("1 2 3 /a b"),
+            Some((
+                "/a b".into(),
+                FileState {
+                    cursor_index: 1,
+                    scroll_offset: OffsetUsize::new(2, 3),
+                }
+            ))
+        );
+    }
+}
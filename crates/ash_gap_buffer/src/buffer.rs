@@ -2,7 +2,7 @@ use std::cmp::Ordering;
 use std::{ptr, slice};
 
 use crate::iter::SkipGapIter;
-use crate::raw::RawBuf;
+use crate::raw::{RawBuf, TryReserveError};
 
 pub struct GapBuffer {
     inner: RawBuf,
@@ -90,6 +90,16 @@ impl GapBuffer {
         };
     }
 
+    #[inline]
+    pub fn push_str(&mut self, s: &str) {
+        self.push_slice(s.as_bytes());
+    }
+
+    #[inline]
+    pub fn push_str_back(&mut self, s: &str) {
+        self.push_slice_back(s.as_bytes());
+    }
+
     #[inline]
     pub fn pop(&mut self) -> Option<u8> {
         if self.front_len > 0 {
@@ -210,6 +220,74 @@ impl GapBuffer {
             .map(|ptr| unsafe { &mut *ptr.cast_mut() })
     }
 
+    /// Get mutable references to the bytes at several logical indices at
+    /// once. Returns `None` if any index is out of bounds, or if any two
+    /// indices are the same.
+    pub fn get_disjoint_mut<const N: usize>(
+        &mut self,
+        indices: [usize; N],
+    ) -> Option<[&mut u8; N]> {
+        for (i, index) in indices.iter().enumerate() {
+            if indices[..i].contains(index) {
+                return None;
+            }
+        }
+
+        let mut ptrs = [ptr::null::<u8>(); N];
+        for (ptr, &index) in ptrs.iter_mut().zip(&indices) {
+            *ptr = self.index_to_ptr(index)?;
+        }
+
+        // The indices are distinct, so none of the references alias, and
+        // they borrow `self` mutably for as long as they live.
+        Some(ptrs.map(|ptr| unsafe { &mut *ptr.cast_mut() }))
+    }
+
+    /// Swap the bytes at two logical indices.
+    ///
+    /// # Panics
+    /// Panics if either index is out of bounds.
+    #[inline]
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let a = self
+            .index_to_ptr(a)
+            .expect("index out of bounds")
+            .cast_mut();
+        let b = self
+            .index_to_ptr(b)
+            .expect("index out of bounds")
+            .cast_mut();
+
+        // `ptr::swap` allows the pointers to be equal.
+        unsafe { ptr::swap(a, b) };
+    }
+
+    /// Remove the byte at `index`, replacing it with the last byte.
+    ///
+    /// This is `O(1)` if the gap is at the end of the buffer, otherwise the
+    /// back of the buffer is shifted by one.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> u8 {
+        let len = self.len();
+        assert!(index < len, "index out of bounds");
+
+        self.swap(index, len - 1);
+
+        if self.back_len == 0 {
+            return self.pop().unwrap();
+        }
+
+        let back_ptr = self.back_ptr().cast_mut();
+        let byte = unsafe { ptr::read(back_ptr.add(self.back_len - 1)) };
+
+        unsafe { ptr::copy(back_ptr, back_ptr.add(1), self.back_len - 1) };
+        self.back_len -= 1;
+
+        byte
+    }
+
     #[inline]
     pub fn clear(&mut self) {
         self.front_len = 0;
@@ -226,6 +304,85 @@ impl GapBuffer {
         self.back_len = self.back_len.min(len);
     }
 
+    /// Binary search a sorted buffer for `x`, like [`slice::binary_search`].
+    ///
+    /// Returns the index of a matching byte, or the index where `x` could be
+    /// inserted to keep the buffer sorted. Indices are logical, i.e. they
+    /// skip the gap.
+    pub fn binary_search(&self, x: &u8) -> Result<usize, usize> {
+        match self.back().first() {
+            Some(first) if x >= first => {
+                let offset = self.front_len;
+                self.back()
+                    .binary_search(x)
+                    .map(|i| i + offset)
+                    .map_err(|i| i + offset)
+            }
+            _ => self.front().binary_search(x),
+        }
+    }
+
+    /// Shorten the buffer to `len` bytes, dropping the rest. Does nothing if
+    /// the buffer is already no longer than `len`.
+    ///
+    /// This leaves the gap at the end of the buffer.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len() {
+            return;
+        }
+
+        if len <= self.front_len {
+            self.front_len = len;
+        } else {
+            self.set_gap(len);
+        }
+
+        self.back_len = 0;
+    }
+
+    /// Set every byte in the buffer to `value`, without moving the gap.
+    #[inline]
+    pub fn fill(&mut self, value: u8) {
+        let (front, back) = self.front_and_back_mut();
+        front.fill(value);
+        back.fill(value);
+    }
+
+    /// Set every byte in the buffer to the results of calling `f`, in order,
+    /// without moving the gap.
+    pub fn fill_with(&mut self, mut f: impl FnMut() -> u8) {
+        let (front, back) = self.front_and_back_mut();
+        front.fill_with(&mut f);
+        back.fill_with(f);
+    }
+
+    /// Resize the buffer to `new_len` bytes, either truncating it or filling
+    /// the end with `value`.
+    ///
+    /// This moves the gap to the end of the buffer.
+    pub fn resize(&mut self, new_len: usize, value: u8) {
+        self.resize_with(new_len, || value);
+    }
+
+    /// Resize the buffer to `new_len` bytes, either truncating it or filling
+    /// the end with the results of calling `f`.
+    ///
+    /// This moves the gap to the end of the buffer.
+    pub fn resize_with(&mut self, new_len: usize, mut f: impl FnMut() -> u8) {
+        let len = self.len();
+
+        if new_len <= len {
+            self.truncate(new_len);
+        } else {
+            self.set_gap(len);
+            self.reserve(new_len - len);
+
+            for _ in len..new_len {
+                self.push(f());
+            }
+        }
+    }
+
     /// # Panics
     /// Panics if `new_cap > isize::MAX`.
     pub fn reserve(&mut self, additional: usize) {
@@ -235,15 +392,37 @@ impl GapBuffer {
             .expect("capacity overflow");
 
         if let Some(new_cap) = calc_new_capacity(self.capacity(), required) {
-            let prev_back_offset = self.inner.capacity() - self.back_len;
+            self.grow(new_cap);
+        }
+    }
 
-            self.inner.set_capacity(new_cap);
+    /// Like [`GapBuffer::reserve`], but returns an error instead of panicking
+    /// if the capacity overflows or aborting if the allocation fails.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let required = self
+            .len()
+            .checked_add(additional)
+            .ok_or(TryReserveError::CapacityOverflow)?;
 
-            // Use offset to get previous back pointer because the buffer could have moved.
-            let prev_back_ptr = unsafe { self.front_ptr().add(prev_back_offset) };
-            let back_ptr = self.back_ptr().cast_mut();
+        match calc_new_capacity(self.capacity(), required) {
+            Some(new_cap) => self.try_grow(new_cap),
+            None => Ok(()),
+        }
+    }
 
-            unsafe { ptr::copy(prev_back_ptr, back_ptr, self.back_len) };
+    /// Like [`GapBuffer::reserve`], but grows the capacity to exactly the
+    /// required length, without any extra space for future pushes.
+    ///
+    /// # Panics
+    /// Panics if `new_cap > isize::MAX`.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        let required = self
+            .len()
+            .checked_add(additional)
+            .expect("capacity overflow");
+
+        if required > self.capacity() {
+            self.grow(required);
         }
     }
 
@@ -275,16 +454,33 @@ impl GapBuffer {
         SkipGapIter::new(front.iter_mut(), back.iter_mut())
     }
 
+    /// Iterate over the bytes along with their logical indices, counting from
+    /// the start of the buffer regardless of where the gap is.
+    #[inline]
+    pub fn indexed(&self) -> impl DoubleEndedIterator<Item = (usize, &u8)> + ExactSizeIterator {
+        self.iter().enumerate()
+    }
+
+    /// The contiguous chunks of the buffer, either side of the gap. Empty
+    /// chunks are skipped.
+    #[inline]
+    pub fn chunks(&self) -> impl DoubleEndedIterator<Item = &[u8]> {
+        [self.front(), self.back()]
+            .into_iter()
+            .filter(|chunk| !chunk.is_empty())
+    }
+
     #[inline]
     pub fn into_vec(mut self) -> Vec<u8> {
+        self.shrink_to_fit();
+
         // `Vec` should handle this case (dangling pointer) fine, but the invariants of
-        // `Vec::from_raw_parts` don't mention it so we'll avoid it.
+        // `Vec::from_raw_parts` don't mention it so we'll avoid it. Checked after
+        // shrinking, as an empty buffer with capacity has none left after that.
         if self.capacity() == 0 {
             return vec![];
         }
 
-        self.shrink_to_fit();
-
         // Safety: all invariants upheld by data structure and above `shrink_to_fit`
         // call.
         let v = unsafe {
@@ -296,6 +492,31 @@ impl GapBuffer {
         v
     }
 
+    fn grow(&mut self, new_cap: usize) {
+        let prev_back_offset = self.inner.capacity() - self.back_len;
+
+        self.inner.set_capacity(new_cap);
+        self.move_back_after_grow(prev_back_offset);
+    }
+
+    fn try_grow(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        let prev_back_offset = self.inner.capacity() - self.back_len;
+
+        self.inner.try_set_capacity(new_cap)?;
+        self.move_back_after_grow(prev_back_offset);
+
+        Ok(())
+    }
+
+    /// Move the back to the end of the buffer after growing it.
+    fn move_back_after_grow(&mut self, prev_back_offset: usize) {
+        // Use offset to get previous back pointer because the buffer could have moved.
+        let prev_back_ptr = unsafe { self.front_ptr().add(prev_back_offset) };
+        let back_ptr = self.back_ptr().cast_mut();
+
+        unsafe { ptr::copy(prev_back_ptr, back_ptr, self.back_len) };
+    }
+
     #[inline]
     pub(crate) fn front_len(&self) -> usize {
         self.front_len
@@ -337,6 +558,25 @@ impl GapBuffer {
     }
 }
 
+impl Clone for GapBuffer {
+    /// Clones the buffer, preserving the position of the gap.
+    fn clone(&self) -> Self {
+        let mut buf = Self::with_capacity(self.capacity());
+        buf.push_slice(self.front());
+        buf.push_slice_back(self.back());
+        buf
+    }
+}
+
+impl PartialEq for GapBuffer {
+    /// Compares the contents of the buffers, regardless of gap position.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for GapBuffer {}
+
 impl From<Vec<u8>> for GapBuffer {
     #[inline]
     fn from(v: Vec<u8>) -> Self {
@@ -367,6 +607,31 @@ impl<const N: usize> From<&[u8; N]> for GapBuffer {
     }
 }
 
+impl From<&str> for GapBuffer {
+    #[inline]
+    fn from(s: &str) -> Self {
+        s.as_bytes().into()
+    }
+}
+
+impl Extend<u8> for GapBuffer {
+    /// Push the bytes to the front, before the gap.
+    fn extend<I: IntoIterator<Item = u8>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+
+        for byte in iter {
+            self.push(byte);
+        }
+    }
+}
+
+impl<'a> Extend<&'a u8> for GapBuffer {
+    fn extend<I: IntoIterator<Item = &'a u8>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
 impl From<GapBuffer> for Vec<u8> {
     #[inline]
     fn from(buf: GapBuffer) -> Self {
@@ -390,6 +655,7 @@ fn calc_new_capacity(cap: usize, required: usize) -> Option<usize> {
 mod tests {
     use super::GapBuffer;
     use crate::buffer::calc_new_capacity;
+    use crate::raw::TryReserveError;
 
     #[test]
     #[cfg_attr(miri, ignore)]
@@ -425,6 +691,78 @@ mod tests {
         assert_eq!(v.as_slice(), b"hello world");
     }
 
+    #[test]
+    fn into_vec_round_trip() {
+        let v = b"hello world".to_vec();
+
+        let mut buf = GapBuffer::from(v.clone());
+        buf.set_gap(5);
+        buf.push(b',');
+        assert_eq!(buf.pop(), Some(b','));
+
+        let round_tripped = buf.into_vec();
+        assert_eq!(round_tripped, v);
+        assert_eq!(round_tripped.capacity(), round_tripped.len());
+    }
+
+    #[test]
+    fn into_vec_empty() {
+        assert_eq!(GapBuffer::new().into_vec(), b"");
+
+        // Has capacity, but nothing in it.
+        let mut buf = GapBuffer::with_capacity(10);
+        buf.push_slice(b"abc");
+        buf.clear();
+        assert_eq!(buf.into_vec(), b"");
+    }
+
+    #[test]
+    fn try_reserve() {
+        let mut buf = GapBuffer::from(b"hello world");
+        buf.set_gap(5);
+
+        assert_eq!(buf.try_reserve(100), Ok(()));
+        assert!(buf.capacity() >= buf.len() + 100);
+        assert_eq!(buf.front(), b"hello");
+        assert_eq!(buf.back(), b" world");
+    }
+
+    #[test]
+    fn try_reserve_overflow() {
+        let mut buf = GapBuffer::from(b"hello");
+        let capacity = buf.capacity();
+
+        assert_eq!(
+            buf.try_reserve(usize::MAX),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(
+            buf.try_reserve(isize::MAX as usize),
+            Err(TryReserveError::CapacityOverflow)
+        );
+
+        assert_eq!(buf.capacity(), capacity);
+        assert_eq!(buf.front(), b"hello");
+    }
+
+    #[test]
+    fn extend() {
+        let mut buf = GapBuffer::from(vec![b'a', b'b']);
+        buf.set_gap(1);
+        assert_eq!(buf.capacity(), 2);
+
+        buf.extend(0..200u8);
+        assert_eq!(buf.front()[0], b'a');
+        assert!(buf.front()[1..].iter().copied().eq(0..200));
+        assert_eq!(buf.back(), b"b");
+
+        // Grown once, for the whole iterator.
+        assert_eq!(buf.capacity(), 202);
+
+        buf.extend(b"cd");
+        assert!(buf.front().ends_with(b"cd"));
+    }
+
     #[test]
     fn grow() {
         let mut buf = GapBuffer::new();
@@ -448,6 +786,27 @@ mod tests {
         assert_eq!(buf.back(), &[2]);
     }
 
+    #[test]
+    fn reserve_exact() {
+        let mut buf = GapBuffer::new();
+
+        buf.reserve_exact(100);
+        assert_eq!(buf.capacity(), 100);
+
+        buf.push_slice(b"hello");
+        buf.push_slice_back(b" world");
+
+        // already enough capacity
+        buf.reserve_exact(10);
+        assert_eq!(buf.capacity(), 100);
+
+        buf.reserve_exact(100);
+        assert_eq!(buf.capacity(), 111);
+
+        assert_eq!(buf.front(), b"hello");
+        assert_eq!(buf.back(), b" world");
+    }
+
     #[test]
     fn push_pop() {
         let mut buf = GapBuffer::new();
@@ -515,6 +874,27 @@ mod tests {
         assert_eq!(buf.back(), b" world");
     }
 
+    #[test]
+    fn push_str() {
+        let mut buf = GapBuffer::new();
+
+        buf.push_str("£5");
+        buf.push_str_back(" 🐻‍❄️");
+
+        assert_eq!(buf.len(), "£5 🐻‍❄️".len());
+        assert_eq!(buf.front(), "£5".as_bytes());
+        assert_eq!(buf.back(), " 🐻‍❄️".as_bytes());
+    }
+
+    #[test]
+    fn from_str() {
+        let buf = GapBuffer::from("that will be £5");
+
+        assert_eq!(buf.len(), 16);
+        assert_eq!(buf.front(), "that will be £5".as_bytes());
+        assert_eq!(buf.back(), b"");
+    }
+
     #[test]
     fn set_gap() {
         let mut buf = GapBuffer::new();
@@ -643,6 +1023,227 @@ mod tests {
         assert_eq!(buf.get(11), None);
     }
 
+    #[test]
+    fn swap() {
+        let mut buf = GapBuffer::new();
+        buf.push_slice(b"abc");
+        buf.push_slice_back(b"def");
+
+        // across the gap
+        buf.swap(1, 4);
+        assert_eq!(buf.front(), b"aec");
+        assert_eq!(buf.back(), b"dbf");
+
+        // within the front
+        buf.swap(0, 2);
+        assert_eq!(buf.front(), b"cea");
+
+        // same index
+        buf.swap(5, 5);
+        assert_eq!(buf.back(), b"dbf");
+    }
+
+    #[test]
+    fn get_disjoint_mut() {
+        let mut buf = GapBuffer::new();
+        buf.push_slice(b"abc");
+        buf.push_slice_back(b"def");
+
+        // one in the front and one in the back
+        let [a, b] = buf.get_disjoint_mut([1, 4]).unwrap();
+        std::mem::swap(a, b);
+        *a = a.to_ascii_uppercase();
+        assert_eq!(buf.front(), b"aEc");
+        assert_eq!(buf.back(), b"dbf");
+
+        assert_eq!(buf.get_disjoint_mut([0, 2, 0]), None);
+        assert_eq!(buf.get_disjoint_mut([5, 6]), None);
+        assert_eq!(buf.get_disjoint_mut([]), Some([]));
+    }
+
+    #[test]
+    #[should_panic = "index out of bounds"]
+    fn swap_out_of_bounds() {
+        let mut buf = GapBuffer::from(b"abc");
+        buf.swap(0, 3);
+    }
+
+    #[test]
+    fn swap_remove() {
+        let mut buf = GapBuffer::new();
+        buf.push_slice(b"abc");
+        buf.push_slice_back(b"def");
+
+        assert_eq!(buf.swap_remove(1), b'b');
+        assert_eq!(buf.front(), b"afc");
+        assert_eq!(buf.back(), b"de");
+
+        assert_eq!(buf.swap_remove(4), b'e');
+        assert_eq!(buf.back(), b"d");
+
+        buf.set_gap(buf.len());
+        assert_eq!(buf.swap_remove(0), b'a');
+
+        let mut rest: Vec<u8> = buf.iter().copied().collect();
+        rest.sort();
+        assert_eq!(rest, b"cdf");
+    }
+
+    #[test]
+    #[should_panic = "index out of bounds"]
+    fn swap_remove_out_of_bounds() {
+        let mut buf = GapBuffer::new();
+        buf.swap_remove(0);
+    }
+
+    #[test]
+    fn resize() {
+        let mut buf = GapBuffer::new();
+        buf.push_slice(b"abc");
+        buf.push_slice_back(b"def");
+
+        buf.resize(8, b'x');
+        assert_eq!(buf.front(), b"abcdefxx");
+        assert_eq!(buf.back(), b"");
+
+        buf.set_gap(2);
+
+        // shrink within the back
+        buf.resize(4, b'x');
+        assert_eq!(buf.front(), b"abcd");
+        assert_eq!(buf.back(), b"");
+
+        buf.set_gap(2);
+
+        // shrink within the front
+        buf.resize(1, b'x');
+        assert_eq!(buf.front(), b"a");
+        assert_eq!(buf.back(), b"");
+
+        buf.resize(0, b'x');
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn resize_with() {
+        let mut buf = GapBuffer::from(b"ab");
+        buf.set_gap(0);
+
+        let mut next = b'0';
+        buf.resize_with(5, || {
+            next += 1;
+            next
+        });
+
+        assert_eq!(buf.front(), b"ab123");
+        assert_eq!(buf.back(), b"");
+    }
+
+    #[test]
+    fn clone() {
+        let mut buf = GapBuffer::from(b"hello world");
+        buf.set_gap(5);
+
+        let cloned = buf.clone();
+        assert_eq!(cloned.front(), b"hello");
+        assert_eq!(cloned.back(), b" world");
+    }
+
+    #[test]
+    fn eq() {
+        let mut a = GapBuffer::from(b"hello world");
+        let mut b = a.clone();
+        a.set_gap(2);
+        b.set_gap(8);
+        assert!(a == b);
+
+        b.push(b'!');
+        assert!(a != b);
+    }
+
+    #[test]
+    fn chunks() {
+        let mut buf = GapBuffer::from(b"hello world");
+
+        buf.set_gap(5);
+        assert_eq!(
+            buf.chunks().collect::<Vec<_>>(),
+            [b"hello" as &[u8], b" world"]
+        );
+        assert_eq!(buf.chunks().collect::<Vec<_>>().concat(), b"hello world");
+
+        buf.set_gap(0);
+        assert_eq!(buf.chunks().collect::<Vec<_>>(), [b"hello world"]);
+
+        buf.set_gap(11);
+        assert_eq!(buf.chunks().collect::<Vec<_>>(), [b"hello world"]);
+
+        assert_eq!(GapBuffer::new().chunks().count(), 0);
+    }
+
+    #[test]
+    fn binary_search() {
+        let mut buf = GapBuffer::from([1, 3, 5, 7, 9, 11].as_slice());
+        buf.set_gap(3);
+
+        assert_eq!(buf.binary_search(&1), Ok(0));
+        assert_eq!(buf.binary_search(&5), Ok(2));
+        assert_eq!(buf.binary_search(&7), Ok(3));
+        assert_eq!(buf.binary_search(&11), Ok(5));
+
+        assert_eq!(buf.binary_search(&0), Err(0));
+        assert_eq!(buf.binary_search(&6), Err(3));
+        assert_eq!(buf.binary_search(&8), Err(4));
+        assert_eq!(buf.binary_search(&12), Err(6));
+
+        // Inserting at the returned index keeps the buffer sorted.
+        let index = buf.binary_search(&8).unwrap_err();
+        buf.set_gap(index);
+        buf.push(8);
+        assert!(buf.iter().is_sorted());
+
+        assert_eq!(GapBuffer::new().binary_search(&1), Err(0));
+    }
+
+    #[test]
+    fn truncate() {
+        let mut buf = GapBuffer::from(b"hello world".as_slice());
+        buf.set_gap(5);
+
+        buf.truncate(20);
+        assert_eq!(buf.len(), 11);
+
+        buf.truncate(8);
+        assert_eq!(buf.front(), b"hello wo");
+        assert_eq!(buf.back(), b"");
+
+        buf.set_gap(2);
+        buf.truncate(4);
+        assert_eq!(buf.front(), b"hell");
+        assert_eq!(buf.back(), b"");
+
+        buf.truncate(0);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn fill() {
+        let mut buf = GapBuffer::from(b"hello world".as_slice());
+        buf.set_gap(5);
+
+        buf.fill(b'x');
+        assert_eq!(buf.front(), b"xxxxx");
+        assert_eq!(buf.back(), b"xxxxxx");
+
+        let mut next = b'a';
+        buf.fill_with(|| {
+            next += 1;
+            next - 1
+        });
+        assert_eq!(buf.front(), b"abcde");
+        assert_eq!(buf.back(), b"fghijk");
+    }
+
     #[test]
     fn iterators() {
         let mut buf = GapBuffer::new();
@@ -663,6 +1264,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn indexed() {
+        let mut buf = GapBuffer::from("hello world");
+        buf.set_gap(5);
+
+        let indexed: Vec<_> = buf.indexed().map(|(i, &b)| (i, b)).collect();
+        let expected: Vec<_> = b"hello world".iter().copied().enumerate().collect();
+        assert_eq!(indexed, expected);
+
+        for (i, &b) in buf.indexed() {
+            assert_eq!(buf.get(i), Some(&b));
+        }
+
+        assert_eq!(buf.indexed().next_back(), Some((10, &b'd')));
+    }
+
     #[test]
     fn shrink_to() {
         let mut buf = GapBuffer::new();
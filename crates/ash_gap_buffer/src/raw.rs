@@ -1,11 +1,53 @@
 use std::alloc::{self, Layout};
+use std::fmt;
 use std::ptr::NonNull;
 
+/// The error returned by fallible allocation methods such as
+/// [`GapBuffer::try_reserve`](crate::buffer::GapBuffer::try_reserve).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TryReserveError {
+    /// The required capacity was larger than `isize::MAX`.
+    CapacityOverflow,
+    /// The allocator failed to allocate the memory.
+    AllocError { layout: Layout },
+}
+
+impl TryReserveError {
+    /// Panic, or for an allocation failure, abort, like the infallible
+    /// allocation methods do.
+    fn handle(self) -> ! {
+        match self {
+            Self::CapacityOverflow => panic!("capacity overflows `isize::MAX`"),
+            Self::AllocError { layout } => alloc::handle_alloc_error(layout),
+        }
+    }
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CapacityOverflow => write!(f, "capacity overflows `isize::MAX`"),
+            Self::AllocError { layout } => {
+                write!(f, "memory allocation of {} bytes failed", layout.size())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TryReserveError {}
+
 pub struct RawBuf {
     ptr: NonNull<u8>,
     cap: usize,
 }
 
+impl Default for RawBuf {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RawBuf {
     #[inline]
     pub const fn new() -> Self {
@@ -27,13 +69,20 @@ impl RawBuf {
     /// # Panics
     /// Panics if `new_cap > isize::MAX`.
     pub fn set_capacity(&mut self, new_cap: usize) {
-        assert!(
-            new_cap <= isize::MAX as usize,
-            "capacity overflows `isize::MAX`"
-        );
+        if let Err(err) = self.try_set_capacity(new_cap) {
+            err.handle();
+        }
+    }
+
+    /// Like [`RawBuf::set_capacity`], but returns an error instead of
+    /// panicking or aborting. The buffer is left unchanged on error.
+    pub fn try_set_capacity(&mut self, new_cap: usize) -> Result<(), TryReserveError> {
+        if new_cap > isize::MAX as usize {
+            return Err(TryReserveError::CapacityOverflow);
+        }
 
         if self.cap == new_cap {
-            return;
+            return Ok(());
         }
 
         if new_cap == 0 {
@@ -50,10 +99,12 @@ impl RawBuf {
             };
 
             self.ptr =
-                NonNull::new(new_ptr).unwrap_or_else(|| alloc::handle_alloc_error(new_layout));
+                NonNull::new(new_ptr).ok_or(TryReserveError::AllocError { layout: new_layout })?;
         }
 
         self.cap = new_cap;
+
+        Ok(())
     }
 
     #[inline]
@@ -99,7 +150,7 @@ impl Drop for RawBuf {
 
 #[cfg(test)]
 mod tests {
-    use super::RawBuf;
+    use super::{RawBuf, TryReserveError};
 
     #[test]
     fn reallocate() {
@@ -136,4 +187,18 @@ mod tests {
     fn cap_too_large() {
         RawBuf::with_capacity(isize::MAX as usize + 1);
     }
+
+    #[test]
+    fn try_set_capacity() {
+        let mut buf = RawBuf::new();
+
+        assert_eq!(
+            buf.try_set_capacity(isize::MAX as usize + 1),
+            Err(TryReserveError::CapacityOverflow)
+        );
+        assert_eq!(buf.capacity(), 0);
+
+        assert_eq!(buf.try_set_capacity(10), Ok(()));
+        assert_eq!(buf.capacity(), 10);
+    }
 }
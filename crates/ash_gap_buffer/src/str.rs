@@ -1,9 +1,12 @@
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
 use std::str::{Bytes, Chars, Utf8Error};
 
 use crate::buffer::GapBuffer;
 use crate::iter::SkipGapIter;
+use crate::raw::TryReserveError;
 
-#[derive(Default)]
+#[derive(Default, Clone, PartialEq, Eq)]
 pub struct GapString {
     inner: GapBuffer,
 }
@@ -77,6 +80,29 @@ impl GapString {
         Some(ch)
     }
 
+    /// Insert a character at `index`, moving the gap to just after it.
+    #[inline]
+    pub fn insert(&mut self, index: usize, ch: char) {
+        self.set_gap(index);
+        self.push(ch);
+    }
+
+    /// Insert a string at `index`, moving the gap to just after it. Space is
+    /// reserved for all of it at once.
+    #[inline]
+    pub fn insert_str(&mut self, index: usize, s: &str) {
+        self.set_gap(index);
+        self.push_str(s);
+    }
+
+    /// Remove the character at `index`, moving the gap to where it was.
+    #[inline]
+    pub fn remove(&mut self, index: usize) -> char {
+        assert!(index < self.len(), "index out of bounds");
+        self.set_gap(index);
+        self.pop_back().expect("index in bounds")
+    }
+
     #[inline]
     pub fn set_gap(&mut self, index: usize) {
         assert!(index <= self.len(), "index out of bounds");
@@ -155,6 +181,11 @@ impl GapString {
         self.inner.reserve(additional);
     }
 
+    #[inline]
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.inner.try_reserve(additional)
+    }
+
     #[inline]
     pub fn shrink_to_fit(&mut self) {
         self.inner.shrink_to_fit();
@@ -166,17 +197,17 @@ impl GapString {
     }
 
     #[inline]
-    pub fn bytes(&self) -> SkipGapIter<Bytes> {
+    pub fn bytes(&self) -> SkipGapIter<Bytes<'_>> {
         SkipGapIter::new(self.front().bytes(), self.back().bytes())
     }
 
     #[inline]
-    pub fn chars(&self) -> SkipGapIter<Chars> {
+    pub fn chars(&self) -> SkipGapIter<Chars<'_>> {
         SkipGapIter::new(self.front().chars(), self.back().chars())
     }
 
     #[inline]
-    pub fn char_indices(&self) -> SkipGapIter<crate::iter::CharIndices> {
+    pub fn char_indices(&self) -> SkipGapIter<crate::iter::CharIndices<'_>> {
         use crate::iter::CharIndices;
 
         let front = CharIndices::new(self.front(), 0);
@@ -185,6 +216,37 @@ impl GapString {
         SkipGapIter::new(front, back)
     }
 
+    /// The contiguous chunks of the string, either side of the gap. Empty
+    /// chunks are skipped.
+    #[inline]
+    pub fn chunks(&self) -> impl DoubleEndedIterator<Item = &str> {
+        [self.front(), self.back()]
+            .into_iter()
+            .filter(|chunk| !chunk.is_empty())
+    }
+
+    /// Replace every occurrence of `from` with `to`, returning the number of
+    /// replacements. Nothing is replaced if `from` is empty.
+    ///
+    /// This is O(n), as it rebuilds the whole string if there are any matches.
+    /// The gap is moved to the end if anything is replaced.
+    pub fn replace_all(&mut self, from: &str, to: &str) -> usize {
+        if from.is_empty() {
+            return 0;
+        }
+
+        let mut s = String::with_capacity(self.len());
+        s.push_str(self.front());
+        s.push_str(self.back());
+
+        let count = s.matches(from).count();
+        if count > 0 {
+            *self = Self::from(s.replace(from, to));
+        }
+
+        count
+    }
+
     #[inline]
     pub fn into_string(self) -> String {
         let bytes = self.inner.into_vec();
@@ -197,6 +259,33 @@ impl GapString {
     }
 }
 
+impl Hash for GapString {
+    /// Hashes the contents of the string, regardless of gap position.
+    ///
+    /// This writes the same bytes as hashing the equivalent `str`, so for
+    /// streaming hashers such as `DefaultHasher` the hashes match.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write(self.front().as_bytes());
+        state.write(self.back().as_bytes());
+        state.write_u8(0xff);
+    }
+}
+
+impl PartialOrd for GapString {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GapString {
+    /// Compares the contents of the strings lexicographically, regardless of
+    /// gap position.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bytes().cmp(other.bytes())
+    }
+}
+
 impl From<String> for GapString {
     #[inline]
     fn from(value: String) -> Self {
@@ -248,6 +337,9 @@ fn is_utf8_char_boundary(byte: u8) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+    use std::hash::{BuildHasher, RandomState};
+
     use super::GapString;
 
     #[test]
@@ -278,6 +370,68 @@ mod tests {
         assert_eq!(s.pop_back(), None);
     }
 
+    #[test]
+    fn insert_remove() {
+        let mut s = GapString::from("ab");
+
+        s.insert(1, '£');
+        assert_eq!(s.front(), "a£");
+        assert_eq!(s.back(), "b");
+
+        s.insert(4, 'c');
+        assert_eq!(s.front(), "a£bc");
+        assert_eq!(s.back(), "");
+
+        assert_eq!(s.remove(1), '£');
+        assert_eq!(s.front(), "a");
+        assert_eq!(s.back(), "bc");
+
+        assert_eq!(s.remove(0), 'a');
+        assert_eq!(s.front(), "");
+        assert_eq!(s.back(), "bc");
+    }
+
+    #[test]
+    fn insert_str() {
+        let mut s = GapString::from("hello world");
+
+        s.insert_str(5, ",");
+        assert_eq!(s.front(), "hello,");
+        assert_eq!(s.back(), " world");
+
+        s.insert_str(0, "");
+        assert_eq!(s.front(), "");
+        assert_eq!(s.back(), "hello, world");
+    }
+
+    #[test]
+    fn insert_str_grows_once() {
+        let mut s = GapString::from("a".repeat(100).as_str());
+        s.shrink_to_fit();
+
+        let long = "£".repeat(5 * 1024);
+        s.insert_str(50, &long);
+
+        // Growing for each char would overshoot the exact size.
+        assert_eq!(s.capacity(), 100 + long.len());
+        assert_eq!(s.len(), 100 + long.len());
+        assert_eq!(s.front().len(), 50 + long.len());
+    }
+
+    #[test]
+    #[should_panic = "index not on char boundary"]
+    fn insert_not_char_boundary() {
+        let mut s = GapString::from("£");
+        s.insert(1, 'a');
+    }
+
+    #[test]
+    #[should_panic = "index out of bounds"]
+    fn remove_out_of_bounds() {
+        let mut s = GapString::from("ab");
+        s.remove(2);
+    }
+
     #[test]
     fn chars() {
         let mut s = GapString::new();
@@ -376,4 +530,71 @@ mod tests {
 
         s.truncate_back(9);
     }
+
+    #[test]
+    fn replace_all() {
+        let mut s = GapString::from("one fish two fish");
+        s.set_gap(6);
+
+        assert_eq!(s.replace_all("fish", "cat"), 2);
+        assert_eq!(s.clone().into_string(), "one cat two cat");
+
+        assert_eq!(s.replace_all("dog", "cat"), 0);
+        assert_eq!(s.clone().into_string(), "one cat two cat");
+
+        assert_eq!(s.replace_all("", "x"), 0);
+    }
+
+    #[test]
+    fn replace_all_changes_length() {
+        let mut s = GapString::from("a£b£c");
+        s.set_gap(3);
+
+        assert_eq!(s.replace_all("£", "pounds"), 2);
+        assert_eq!(s.len(), 15);
+        assert_eq!(s.clone().into_string(), "apoundsbpoundsc");
+
+        // Matches are found across the gap.
+        let mut s = GapString::from("aaaa");
+        s.set_gap(1);
+        assert_eq!(s.replace_all("aa", "b"), 2);
+        assert_eq!(s.into_string(), "bb");
+    }
+
+    #[test]
+    fn eq_ignores_gap() {
+        let mut a = GapString::from("hello world");
+        let mut b = a.clone();
+        a.set_gap(3);
+        b.set_gap(9);
+
+        assert!(a == b);
+        assert!(a != GapString::from("hello"));
+    }
+
+    #[test]
+    fn ord_ignores_gap() {
+        let mut a = GapString::from("abc");
+        let mut b = GapString::from("abd");
+        a.set_gap(1);
+        b.set_gap(3);
+
+        assert!(a < b);
+        assert!(GapString::from("ab") < a);
+    }
+
+    #[test]
+    fn hash() {
+        let state = RandomState::new();
+
+        let mut s = GapString::from("hello £ world");
+        s.set_gap(8);
+        assert_eq!(state.hash_one(&s), state.hash_one("hello £ world"));
+
+        let mut set = HashSet::new();
+        set.insert(s.clone());
+        s.set_gap(2);
+        assert!(set.contains(&s));
+        assert!(!set.insert(s));
+    }
 }
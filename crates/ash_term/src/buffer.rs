@@ -6,13 +6,14 @@ use unicode_width::UnicodeWidthStr;
 
 // use unicode_segmentation::UnicodeSegmentation;
 // use unicode_width::UnicodeWidthStr;
-use crate::style::{CursorStyle, Style};
-use crate::units::OffsetU16;
+use crate::style::{CursorStyle, Style, Weight};
+use crate::units::{OffsetU16, Rect};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Cell {
     grapheme: CompactString,
     style: Style,
+    link: Option<CompactString>,
 }
 
 impl Cell {
@@ -20,6 +21,7 @@ impl Cell {
         Cell {
             grapheme: CompactString::new_inline(" "),
             style: Style::EMPTY,
+            link: None,
         }
     }
 
@@ -31,6 +33,11 @@ impl Cell {
         self.style
     }
 
+    /// The hyperlink target of the cell, if any.
+    pub fn link(&self) -> Option<&str> {
+        self.link.as_deref()
+    }
+
     pub fn with_grapheme(mut self, grapheme: &str) -> Self {
         self.grapheme = grapheme.to_compact_string();
         self
@@ -45,6 +52,11 @@ impl Cell {
         self.style = style;
         self
     }
+
+    pub fn with_link(mut self, link: Option<&str>) -> Self {
+        self.link = link.map(CompactString::from);
+        self
+    }
 }
 
 impl Default for Cell {
@@ -100,12 +112,44 @@ impl Buffer {
         }
     }
 
+    #[inline]
+    pub fn size(&self) -> OffsetU16 {
+        self.size
+    }
+
+    /// Render the graphemes in the buffer as lines of text, ignoring styles,
+    /// for comparing against in tests. Empty cells are shown as spaces, and
+    /// the cells covered by a wide grapheme are skipped.
+    pub fn to_debug_string(&self) -> String {
+        let mut s = String::new();
+
+        for (y, row) in self.buf.chunks(self.size.x.max(1) as usize).enumerate() {
+            if y > 0 {
+                s.push('\n');
+            }
+
+            let mut skip = 0;
+            for cell in row {
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+
+                let grapheme = cell.as_ref().map_or(" ", Cell::grapheme);
+                s.push_str(grapheme);
+                skip = grapheme.width().saturating_sub(1);
+            }
+        }
+
+        s
+    }
+
     pub fn resize_and_clear(&mut self, size: impl Into<OffsetU16>) {
         let size: OffsetU16 = size.into();
 
         if size != self.size {
             self.buf.clear();
-            self.buf.extend(std::iter::repeat(None).take(size.area()));
+            self.buf.extend(std::iter::repeat_n(None, size.area()));
             self.size = size;
         } else {
             self.buf.fill(None);
@@ -118,10 +162,41 @@ impl Buffer {
         self.buf.fill(Some(cell));
     }
 
-    pub fn view(&mut self, set_cursor: bool) -> BufferView {
+    /// Copy a block of cells so its top left corner is at `dst_origin`, for
+    /// scrolling without redrawing. Like `memmove`, the source and
+    /// destination may overlap. Anything that falls outside the buffer is
+    /// skipped, and source cells that aren't overwritten are left as they
+    /// were.
+    pub fn copy_region(&mut self, src: Rect, dst_origin: OffsetU16) {
+        let src = src.intersect(Rect::from_size(OffsetU16::ZERO, self.size));
+        let size = src.size().min(self.size.saturating_sub(dst_origin));
+        if size.area() == 0 {
+            return;
+        }
+
+        let width = self.size.x as usize;
+        let row_range = |start: OffsetU16, y: u16| {
+            let row_start = (start.y + y) as usize * width + start.x as usize;
+            row_start..row_start + size.x as usize
+        };
+
+        let copy_row = |buf: &mut Vec<Option<Cell>>, y: u16| {
+            let row = buf[row_range(src.start, y)].to_vec();
+            buf[row_range(dst_origin, y)].clone_from_slice(&row);
+        };
+
+        // Copy rows in the direction that doesn't overwrite rows still to be
+        // copied.
+        if dst_origin.y > src.start.y {
+            (0..size.y).rev().for_each(|y| copy_row(&mut self.buf, y));
+        } else {
+            (0..size.y).for_each(|y| copy_row(&mut self.buf, y));
+        }
+    }
+
+    pub fn view(&mut self, set_cursor: bool) -> BufferView<'_> {
         BufferView {
-            start: OffsetU16::ZERO,
-            end: self.size,
+            rect: Rect::from_size(OffsetU16::ZERO, self.size),
             buf: self,
             set_cursor,
         }
@@ -130,10 +205,7 @@ impl Buffer {
 
 pub struct BufferView<'a> {
     buf: &'a mut Buffer,
-
-    start: OffsetU16,
-    end: OffsetU16,
-
+    rect: Rect,
     set_cursor: bool,
 }
 
@@ -143,33 +215,52 @@ impl<'a> BufferView<'a> {
         x: impl RangeBounds<u16>,
         y: impl RangeBounds<u16>,
         set_cursor: bool,
-    ) -> BufferView {
+    ) -> BufferView<'_> {
         let x = bounds_within_domain(x, 0..self.size().x);
         let y = bounds_within_domain(y, 0..self.size().y);
 
+        let start = self.rect.start;
+
         BufferView {
             buf: self.buf,
-            start: OffsetU16::new(x.0, y.0),
-            end: OffsetU16::new(x.1, y.1),
+            rect: Rect::new(
+                start + OffsetU16::new(x.0, y.0),
+                start + OffsetU16::new(x.1, y.1),
+            ),
             set_cursor: set_cursor && self.set_cursor,
         }
     }
 
+    /// A sub-view covering `rect`, which is relative to this view and clamped
+    /// to it.
+    pub fn view_rect(&mut self, rect: Rect, set_cursor: bool) -> BufferView<'_> {
+        self.view(
+            rect.start.x..rect.end.x,
+            rect.start.y..rect.end.y,
+            set_cursor,
+        )
+    }
+
+    /// The area of this view, relative to itself.
+    pub fn rect(&self) -> Rect {
+        Rect::from_size(OffsetU16::ZERO, self.size())
+    }
+
     pub fn size(&self) -> OffsetU16 {
-        self.end - self.start
+        self.rect.size()
     }
 
     pub fn draw_text(&mut self, pos: impl Into<OffsetU16>, text: &str, style: Style) {
-        let mut pos = self.start.saturating_add(pos.into());
+        let mut pos = self.rect.start.saturating_add(pos.into());
 
-        if pos.y >= self.end.y {
+        if pos.y >= self.rect.end.y {
             return;
         }
 
         let line_start_index = pos.y as usize * self.buf.size.x as usize;
 
         for grapheme in text.graphemes(true) {
-            if pos.x >= self.end.x {
+            if pos.x >= self.rect.end.x {
                 break;
             }
 
@@ -191,6 +282,47 @@ impl<'a> BufferView<'a> {
         }
     }
 
+    /// Dim every cell in this view, such as to make a backdrop for something
+    /// drawn over it.
+    pub fn dim(&mut self) {
+        let size = self.size();
+
+        for y in 0..size.y {
+            for x in 0..size.x {
+                if let Some(cell) = &mut self[[x, y]] {
+                    cell.style.weight = Weight::Dim;
+                }
+            }
+        }
+    }
+
+    /// Draw a border around the edge of this view, clearing everything inside
+    /// it.
+    pub fn draw_box(&mut self, style: Style) {
+        let OffsetU16 { x: w, y: h } = self.size();
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        for y in 0..h {
+            for x in 0..w {
+                let ch = match (x, y) {
+                    (0, 0) => '┌',
+                    (x, 0) if x == w - 1 => '┐',
+                    (0, y) if y == h - 1 => '└',
+                    (x, y) if x == w - 1 && y == h - 1 => '┘',
+                    (_, 0) => '─',
+                    (_, y) if y == h - 1 => '─',
+                    (0, _) => '│',
+                    (x, _) if x == w - 1 => '│',
+                    _ => ' ',
+                };
+
+                self[[x, y]] = Some(Cell::empty().with_char(ch).with_style(style));
+            }
+        }
+    }
+
     pub fn get(&self, index: impl Into<OffsetU16>) -> Option<&Option<Cell>> {
         self.buf.buf.get(self.index(index)?)
     }
@@ -208,9 +340,9 @@ impl<'a> BufferView<'a> {
         let cursor = match cursor {
             Some(cursor) => {
                 let cursor: OffsetU16 = cursor.into();
-                let cursor = cursor.saturating_add(self.start);
+                let cursor = cursor.saturating_add(self.rect.start);
 
-                if cursor.cmp_lt(self.end).both() {
+                if self.rect.contains(cursor) {
                     Some(cursor)
                 } else {
                     None
@@ -238,9 +370,9 @@ impl<'a> BufferView<'a> {
     }
 
     fn index(&self, index: impl Into<OffsetU16>) -> Option<usize> {
-        let index = self.start.saturating_add(index.into());
+        let index = self.rect.start.saturating_add(index.into());
 
-        if index.cmp_gt(self.end).either() {
+        if !self.rect.contains(index) {
             return None;
         }
 
@@ -264,24 +396,29 @@ impl<I: Into<OffsetU16>> IndexMut<I> for BufferView<'_> {
 
 fn bounds_within_domain(bounds: impl RangeBounds<u16>, domain: Range<u16>) -> (u16, u16) {
     let start = match bounds.start_bound() {
-        Bound::Included(&start) => (domain.start + start).min(domain.end),
-        Bound::Excluded(&start) => (domain.start + start + 1).min(domain.end),
+        Bound::Included(&start) => domain.start.saturating_add(start),
+        Bound::Excluded(&start) => domain.start.saturating_add(start).saturating_add(1),
         Bound::Unbounded => domain.start,
-    };
+    }
+    .min(domain.end);
 
     let end = match bounds.end_bound() {
-        Bound::Included(&end) => (domain.start + end + 1).min(domain.end),
-        Bound::Excluded(&end) => (domain.start + end).min(domain.end),
+        Bound::Included(&end) => domain.start.saturating_add(end).saturating_add(1),
+        Bound::Excluded(&end) => domain.start.saturating_add(end),
         Bound::Unbounded => domain.end,
-    };
+    }
+    .clamp(start, domain.end);
 
     (start, end)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Buffer, Cell};
-    // use crate::style::Style;
+    use std::ops::Bound;
+
+    use super::{bounds_within_domain, Buffer, Cell};
+    use crate::style::{Color, Style, Weight};
+    use crate::units::{OffsetU16, Rect};
 
     #[test]
     fn simple() {
@@ -299,6 +436,170 @@ mod tests {
         assert!(buf.get([10, 10]).is_none());
     }
 
+    #[test]
+    fn to_debug_string() {
+        let mut buff = Buffer::new([4, 3]);
+        let mut buf = buff.view(true);
+
+        buf.draw_text([0, 0], "ab", Style::EMPTY);
+        buf.draw_text([1, 1], "日c", Style::EMPTY);
+        buf[[3, 2]] = Some(Cell::empty().with_char('d'));
+
+        assert_eq!(buff.to_debug_string(), "ab  \n 日c\n   d");
+    }
+
+    #[test]
+    fn dim() {
+        let mut buff = Buffer::new([3, 2]);
+        buff.fill(Cell::empty().with_char('x').with_style(Style {
+            fg: Color::Red,
+            ..Style::EMPTY
+        }));
+
+        let mut buf = buff.view(true);
+        buf.view(1.., .., false).dim();
+
+        for y in 0..2 {
+            assert_eq!(buf[[0, y]].as_ref().unwrap().style().weight, Weight::Normal);
+
+            for x in 1..3 {
+                let cell = buf[[x, y]].as_ref().unwrap();
+                assert_eq!(cell.grapheme(), "x");
+                assert_eq!(
+                    cell.style(),
+                    Style {
+                        fg: Color::Red,
+                        weight: Weight::Dim,
+                        ..Style::EMPTY
+                    }
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn draw_box() {
+        let mut buff = Buffer::new([6, 4]);
+        buff.fill(Cell::empty().with_char('x'));
+
+        let mut buf = buff.view(true);
+        buf.view(1..5, 0..3, false).draw_box(Style::EMPTY);
+
+        assert_eq!(buff.to_debug_string(), "x┌──┐x\nx│  │x\nx└──┘x\nxxxxxx");
+    }
+
+    /// A buffer with each row filled with its own letter.
+    fn lettered(size: [u16; 2]) -> Buffer {
+        let mut buff = Buffer::new(size);
+        let mut buf = buff.view(true);
+        for y in 0..size[1] {
+            let ch = char::from(b'a' + y as u8);
+            for x in 0..size[0] {
+                buf[[x, y]] = Some(Cell::empty().with_char(ch));
+            }
+        }
+        buff
+    }
+
+    #[test]
+    fn copy_region_up() {
+        let mut buff = lettered([3, 4]);
+        buff.copy_region(
+            Rect::new(OffsetU16::new(0, 1), OffsetU16::new(3, 4)),
+            OffsetU16::new(0, 0),
+        );
+        assert_eq!(buff.to_debug_string(), "bbb\nccc\nddd\nddd");
+    }
+
+    #[test]
+    fn copy_region_down() {
+        let mut buff = lettered([3, 4]);
+        buff.copy_region(
+            Rect::new(OffsetU16::new(0, 0), OffsetU16::new(3, 3)),
+            OffsetU16::new(0, 1),
+        );
+        assert_eq!(buff.to_debug_string(), "aaa\naaa\nbbb\nccc");
+    }
+
+    #[test]
+    fn copy_region_overlap_sideways() {
+        let mut buff = Buffer::new([5, 1]);
+        buff.view(true).draw_text([0, 0], "abcde", Style::EMPTY);
+
+        buff.copy_region(
+            Rect::new(OffsetU16::new(0, 0), OffsetU16::new(3, 1)),
+            OffsetU16::new(1, 0),
+        );
+        assert_eq!(buff.to_debug_string(), "aabce");
+
+        buff.copy_region(
+            Rect::new(OffsetU16::new(2, 0), OffsetU16::new(5, 1)),
+            OffsetU16::new(1, 0),
+        );
+        assert_eq!(buff.to_debug_string(), "abcee");
+    }
+
+    #[test]
+    fn copy_region_clipped() {
+        let mut buff = lettered([3, 3]);
+        buff.copy_region(
+            Rect::new(OffsetU16::new(0, 0), OffsetU16::new(10, 10)),
+            OffsetU16::new(1, 2),
+        );
+        assert_eq!(buff.to_debug_string(), "aaa\nbbb\ncaa");
+
+        // Nothing lands inside the buffer.
+        buff.copy_region(
+            Rect::new(OffsetU16::new(0, 0), OffsetU16::new(3, 3)),
+            OffsetU16::new(5, 0),
+        );
+        assert_eq!(buff.to_debug_string(), "aaa\nbbb\ncaa");
+    }
+
+    #[test]
+    fn bounds_near_max() {
+        let domain = 10..u16::MAX;
+
+        assert_eq!(
+            bounds_within_domain(..=u16::MAX, domain.clone()),
+            (10, u16::MAX)
+        );
+        assert_eq!(
+            bounds_within_domain(u16::MAX - 1.., domain.clone()),
+            (u16::MAX, u16::MAX)
+        );
+        assert_eq!(
+            bounds_within_domain(
+                (Bound::Excluded(u16::MAX), Bound::Unbounded),
+                domain.clone()
+            ),
+            (u16::MAX, u16::MAX)
+        );
+        assert_eq!(
+            bounds_within_domain((Bound::Included(5), Bound::Excluded(2)), domain),
+            (15, 15)
+        );
+    }
+
+    #[test]
+    fn view_near_max() {
+        let mut buff = Buffer::new([u16::MAX, 2]);
+        let mut buf = buff.view(true);
+
+        let mut view = buf.view(u16::MAX - 2..=u16::MAX, 1.., true);
+        assert_eq!(view.size(), OffsetU16::new(2, 1));
+        assert_eq!(
+            view.rect(),
+            Rect::new(OffsetU16::ZERO, OffsetU16::new(2, 1))
+        );
+
+        view[[1, 0]] = Some(Cell::empty().with_char('a'));
+        assert!(view.get([2, 0]).is_none());
+        assert!(view.get([u16::MAX, u16::MAX]).is_none());
+
+        assert_eq!(buf[[u16::MAX - 1, 1]], Some(Cell::empty().with_char('a')));
+    }
+
     // #[test]
     // fn write_str() {
     //     let mut buff = Buffer::new([10, 10]);
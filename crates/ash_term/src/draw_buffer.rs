@@ -1,7 +1,9 @@
-use crate::buffer::BufferView;
+use std::ops::Range;
+
+use crate::buffer::{Buffer, BufferView};
 use crate::platform::Writer;
 use crate::style::{CursorStyle, Style};
-use crate::units::OffsetU16;
+use crate::units::{OffsetU16, Rect};
 
 pub fn draw_diff(old: &BufferView, new: &BufferView, w: &mut impl Writer) {
     if old.size() != new.size() {
@@ -14,6 +16,7 @@ pub fn draw_diff(old: &BufferView, new: &BufferView, w: &mut impl Writer) {
 
     let mut cursor_pos = OffsetU16::ZERO;
     let mut style = Style::default();
+    let mut link = None;
 
     w.write_style(style);
 
@@ -31,6 +34,9 @@ pub fn draw_diff(old: &BufferView, new: &BufferView, w: &mut impl Writer) {
             draw_style_diff(style, cell.style(), w);
             style = cell.style();
 
+            draw_link_diff(link, cell.link(), w);
+            link = cell.link();
+
             let cell_pos = OffsetU16::new(x, y);
             if cell_pos != cursor_pos {
                 w.set_cursor_pos(cell_pos);
@@ -43,10 +49,64 @@ pub fn draw_diff(old: &BufferView, new: &BufferView, w: &mut impl Writer) {
         }
     }
 
+    draw_link_diff(link, None, w);
+
+    // Reconcile the cursor style even while the cursor is hidden, so the
+    // terminal's style always matches the last frame drawn.
+    draw_cursor_style_diff(old.cursor_style(), new.cursor_style(), w);
+
     if let Some(pos) = new.cursor() {
         w.set_cursor_pos(pos);
         w.set_cursor_vis(true);
-        draw_cursor_style_diff(old.cursor_style(), new.cursor_style(), w);
+    }
+}
+
+/// Scroll the text in `rows` of the terminal by `delta` lines, and `old` to
+/// match, so that a following [`draw_diff`] from `old` only has to draw the
+/// rows scrolled in. A positive `delta` moves the text up.
+///
+/// Nothing is done if the whole of `rows` would be scrolled out.
+pub fn draw_scroll(old: &mut Buffer, rows: Range<u16>, delta: i32, w: &mut impl Writer) {
+    let size = old.size();
+    let rows = rows.start.min(size.y)..rows.end.min(size.y);
+
+    let height = rows.len() as u32;
+    if delta == 0 || delta.unsigned_abs() >= height {
+        return;
+    }
+
+    // The rows scrolled in are blanked with the current background color.
+    w.write_style(Style::default());
+    w.scroll_rows(rows.clone(), delta);
+
+    let shift = delta.unsigned_abs() as u16;
+    let (src, dst_y, exposed) = if delta > 0 {
+        (
+            rows.start + shift..rows.end,
+            rows.start,
+            rows.end - shift..rows.end,
+        )
+    } else {
+        (
+            rows.start..rows.end - shift,
+            rows.start + shift,
+            rows.start..rows.start + shift,
+        )
+    };
+
+    old.copy_region(
+        Rect::new(
+            OffsetU16::new(0, src.start),
+            OffsetU16::new(size.x, src.end),
+        ),
+        OffsetU16::new(0, dst_y),
+    );
+
+    let mut view = old.view(false);
+    for y in exposed {
+        for x in 0..size.x {
+            view[[x, y]] = None;
+        }
     }
 }
 
@@ -61,6 +121,7 @@ fn draw_no_diff(buf: &BufferView, w: &mut impl Writer) {
     let mut style = Style::default();
     w.write_style(style);
 
+    let mut link = None;
     let mut pos_dirty = false;
 
     for y in 0..buf.size().y {
@@ -77,14 +138,20 @@ fn draw_no_diff(buf: &BufferView, w: &mut impl Writer) {
             draw_style_diff(style, cell.style(), w);
             style = cell.style();
 
+            draw_link_diff(link, cell.link(), w);
+            link = cell.link();
+
             w.write_str_raw(cell.grapheme());
         }
 
         pos_dirty = true;
     }
 
+    draw_link_diff(link, None, w);
+
+    w.write_cursor_style(buf.cursor_style());
+
     if let Some(pos) = buf.cursor() {
-        w.write_cursor_style(buf.cursor_style());
         w.set_cursor_pos(pos);
         w.set_cursor_vis(true);
     }
@@ -108,6 +175,12 @@ fn draw_style_diff(old: Style, new: Style, w: &mut impl Writer) {
     }
 }
 
+fn draw_link_diff(old: Option<&str>, new: Option<&str>, w: &mut impl Writer) {
+    if new != old {
+        w.set_link(new);
+    }
+}
+
 fn draw_cursor_style_diff(old: CursorStyle, new: CursorStyle, w: &mut impl Writer) {
     if old.shape != new.shape {
         w.set_cursor_shape(new.shape);
@@ -117,3 +190,128 @@ fn draw_cursor_style_diff(old: CursorStyle, new: CursorStyle, w: &mut impl Write
         w.set_cursor_blinking(new.blinking);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{draw_diff, draw_scroll};
+    use crate::buffer::{Buffer, Cell};
+    use crate::platform::ansi::AnsiWriter;
+    use crate::platform::Writer;
+    use crate::style::{CursorShape, CursorStyle};
+
+    fn diff_output(old: &mut Buffer, new: &mut Buffer) -> String {
+        let mut w = AnsiWriter::new(vec![]);
+        draw_diff(&old.view(false), &new.view(false), &mut w);
+        w.flush().unwrap();
+
+        String::from_utf8(w.inner().clone()).unwrap()
+    }
+
+    #[test]
+    fn link() {
+        let mut old = Buffer::new([5, 1]);
+        let mut new = Buffer::new([5, 1]);
+
+        {
+            let mut view = new.view(false);
+            let link = Some("https://example.com");
+
+            view[[0, 0]] = Some(Cell::empty().with_char('a').with_link(link));
+            view[[1, 0]] = Some(Cell::empty().with_char('b').with_link(link));
+            view[[2, 0]] = Some(Cell::empty().with_char('c'));
+        }
+
+        let output = diff_output(&mut old, &mut new);
+
+        assert!(output.contains("\x1b]8;;https://example.com\x1b\\ab\x1b]8;;\x1b\\c"));
+        assert_eq!(output.matches("\x1b]8;;").count(), 2);
+    }
+
+    #[test]
+    fn cursor_style() {
+        const BLOCK: &str = "\x1b[2 q";
+        const BAR: &str = "\x1b[6 q";
+        const BLINK: &str = "\x1b[?12h";
+        const NO_BLINK: &str = "\x1b[?12l";
+
+        let frame = |cursor: Option<[u16; 2]>, shape, blinking| {
+            let mut buf = Buffer::new([5, 1]);
+            buf.cursor = cursor.map(Into::into);
+            buf.cursor_style = CursorStyle { shape, blinking };
+            buf
+        };
+
+        let mut bar = frame(Some([0, 0]), CursorShape::Bar, false);
+
+        // The style changes while the cursor is hidden.
+        let mut hidden_block = frame(None, CursorShape::Block, false);
+        let output = diff_output(&mut bar, &mut hidden_block);
+        assert!(output.contains(BLOCK));
+        assert!(!output.contains(NO_BLINK) && !output.contains(BLINK));
+
+        // The cursor is shown again, with the style already applied.
+        let mut block = frame(Some([1, 0]), CursorShape::Block, false);
+        let output = diff_output(&mut hidden_block, &mut block);
+        assert!(!output.contains(BLOCK) && !output.contains(BAR));
+
+        let mut blinking_block = frame(Some([1, 0]), CursorShape::Block, true);
+        let output = diff_output(&mut block, &mut blinking_block);
+        assert!(output.contains(BLINK));
+        assert!(!output.contains(BLOCK));
+    }
+
+    #[test]
+    fn scroll() {
+        let lettered = |first: u8| {
+            let mut buf = Buffer::new([80, 10]);
+            let mut view = buf.view(false);
+            for y in 0..9 {
+                let line = char::from(first + y as u8).to_string().repeat(80);
+                view.draw_text([0, y], &line, Default::default());
+            }
+            view.draw_text([0, 9], "status", Default::default());
+            buf
+        };
+
+        let mut old = lettered(b'a');
+        let mut new = lettered(b'b');
+
+        let full = diff_output(&mut old.clone(), &mut new);
+
+        let mut w = AnsiWriter::new(vec![]);
+        draw_scroll(&mut old, 0..9, 1, &mut w);
+        draw_diff(&old.view(false), &new.view(false), &mut w);
+        w.flush().unwrap();
+        let scrolled = String::from_utf8(w.inner().clone()).unwrap();
+
+        assert!(scrolled.contains("\x1b[1;9r\x1b[1S\x1b[r"));
+        assert!(
+            scrolled.len() * 4 < full.len(),
+            "{} vs {} bytes",
+            scrolled.len(),
+            full.len()
+        );
+
+        // Only the row scrolled in is left to draw.
+        assert_eq!(
+            old.to_debug_string(),
+            new.to_debug_string()
+                .replace(&"j".repeat(80), &" ".repeat(80))
+        );
+    }
+
+    #[test]
+    fn scroll_down() {
+        let mut old = Buffer::new([2, 4]);
+        old.view(false).draw_text([0, 0], "ab", Default::default());
+        old.view(false).draw_text([0, 1], "cd", Default::default());
+
+        let mut w = AnsiWriter::new(vec![]);
+        draw_scroll(&mut old, 0..3, -2, &mut w);
+        assert_eq!(old.to_debug_string(), "  \n  \nab\n  ");
+
+        // Scrolling everything out does nothing.
+        draw_scroll(&mut old, 0..3, 3, &mut w);
+        assert_eq!(old.to_debug_string(), "  \n  \nab\n  ");
+    }
+}
@@ -1,9 +1,13 @@
+use std::fmt;
+
 use bitflags::bitflags;
 
 #[derive(Debug, Clone)]
 pub enum Event {
     Key(KeyEvent),
     Paste(String),
+    FocusGained,
+    FocusLost,
     Unknown,
 }
 
@@ -38,6 +42,63 @@ impl KeyEvent {
     }
 }
 
+/// Shown in Vim's notation, so a plain character is shown as itself and
+/// anything else is in angle brackets, like `<C-a>` or `<Esc>`.
+impl fmt::Display for KeyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.key_code {
+            // Terminals report control letters in uppercase.
+            KeyCode::Char(ch) if self.modifiers.contains(Modifiers::CTRL) => {
+                ch.to_ascii_lowercase().to_string()
+            }
+            KeyCode::Char(' ') => "Space".to_owned(),
+            KeyCode::Char('<') => "lt".to_owned(),
+            KeyCode::Char(ch) => ch.to_string(),
+            KeyCode::Fn(n) => format!("F{n}"),
+
+            KeyCode::Tab => "Tab".to_owned(),
+            KeyCode::Newline => "NL".to_owned(),
+            KeyCode::Return => "CR".to_owned(),
+
+            KeyCode::Escape => "Esc".to_owned(),
+
+            KeyCode::Up => "Up".to_owned(),
+            KeyCode::Down => "Down".to_owned(),
+            KeyCode::Right => "Right".to_owned(),
+            KeyCode::Left => "Left".to_owned(),
+
+            KeyCode::End => "End".to_owned(),
+            KeyCode::Home => "Home".to_owned(),
+
+            KeyCode::Insert => "Insert".to_owned(),
+            KeyCode::Delete => "Del".to_owned(),
+            KeyCode::Backspace => "BS".to_owned(),
+
+            KeyCode::PageUp => "PageUp".to_owned(),
+            KeyCode::PageDown => "PageDown".to_owned(),
+        };
+
+        let mut prefix = String::new();
+        for (modifier, letter) in [
+            (Modifiers::CTRL, 'C'),
+            (Modifiers::ALT, 'A'),
+            (Modifiers::SHIFT, 'S'),
+            (Modifiers::META, 'M'),
+        ] {
+            if self.modifiers.contains(modifier) {
+                prefix.push(letter);
+                prefix.push('-');
+            }
+        }
+
+        if prefix.is_empty() && name.chars().count() == 1 {
+            write!(f, "{name}")
+        } else {
+            write!(f, "<{prefix}{name}>")
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum KeyCode {
     Char(char),
@@ -76,3 +137,24 @@ bitflags! {
         const META  = 0b1000;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyCode, KeyEvent, Modifiers};
+
+    #[test]
+    fn display() {
+        let display =
+            |key_code, modifiers| KeyEvent::new_with_mods(key_code, modifiers).to_string();
+
+        assert_eq!(display(KeyCode::Char('x'), Modifiers::EMPTY), "x");
+        assert_eq!(display(KeyCode::Char('<'), Modifiers::EMPTY), "<lt>");
+        assert_eq!(display(KeyCode::Char('A'), Modifiers::CTRL), "<C-a>");
+        assert_eq!(
+            display(KeyCode::Left, Modifiers::ALT | Modifiers::SHIFT),
+            "<A-S-Left>"
+        );
+        assert_eq!(display(KeyCode::Escape, Modifiers::EMPTY), "<Esc>");
+        assert_eq!(display(KeyCode::Fn(5), Modifiers::EMPTY), "<F5>");
+    }
+}
@@ -1,15 +1,35 @@
 use std::fmt::Write as _;
 use std::io::{self, Write};
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthStr;
 
 use super::Writer;
 use crate::style::{Color, CursorShape, Weight};
 use crate::units::OffsetU16;
 
 const CSI: &str = "\x1b[";
+const OSC: &str = "\x1b]";
+const ST: &str = "\x1b\\";
+
+/// The default size the buffer can grow to before it's written out, in
+/// bytes.
+const DEFAULT_FLUSH_THRESHOLD: usize = 1024 * 1024;
 
 pub struct AnsiWriter<W: Write> {
     buf: String,
     writer: W,
+
+    /// Once the buffer reaches this size, it's written to `writer` before
+    /// anything else is added to it.
+    flush_threshold: usize,
+    /// An error from writing the buffer out early, to return from the next
+    /// flush.
+    error: Option<io::Error>,
+
+    /// Where the terminal's cursor is, if known. Used to move it with shorter
+    /// relative sequences.
+    cursor: Option<OffsetU16>,
 }
 
 impl<W: Write> AnsiWriter<W> {
@@ -17,31 +37,67 @@ impl<W: Write> AnsiWriter<W> {
         Self {
             buf: String::new(),
             writer,
+
+            flush_threshold: DEFAULT_FLUSH_THRESHOLD,
+            error: None,
+
+            cursor: None,
         }
     }
 
+    /// Set the size in bytes the buffer can grow to before it's written out
+    /// without waiting for a flush.
+    pub fn with_flush_threshold(mut self, flush_threshold: usize) -> Self {
+        self.flush_threshold = flush_threshold;
+        self
+    }
+
     pub fn inner(&self) -> &W {
         &self.writer
     }
+
+    /// The buffer to write to, after writing it out if it has grown past the
+    /// threshold.
+    #[inline]
+    fn out(&mut self) -> &mut String {
+        if self.buf.len() >= self.flush_threshold {
+            self.write_buf();
+        }
+
+        &mut self.buf
+    }
+
+    fn write_buf(&mut self) {
+        let res = self.writer.write_all(self.buf.as_bytes());
+        self.buf.clear();
+
+        if let Err(err) = res {
+            self.error.get_or_insert(err);
+        }
+    }
 }
 
 impl<W: Write> Writer for AnsiWriter<W> {
     #[inline]
     fn flush(&mut self) -> io::Result<()> {
-        self.writer.write_all(self.buf.as_bytes())?;
-        self.buf.clear();
+        self.write_buf();
+
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
 
         self.writer.flush()
     }
 
     #[inline]
     fn clear_all(&mut self) {
-        write!(self.buf, "{CSI}2J{CSI}3J").unwrap();
+        write!(self.out(), "{CSI}2J{CSI}3J").unwrap();
     }
 
     #[inline]
     fn set_cursor_home(&mut self) {
-        write!(self.buf, "{CSI}H").unwrap();
+        write!(self.out(), "{CSI}H").unwrap();
+        self.cursor = Some(OffsetU16::ZERO);
     }
 
     #[inline]
@@ -51,68 +107,186 @@ impl<W: Write> Writer for AnsiWriter<W> {
         let row = pos.y.saturating_add(1);
         let col = pos.x.saturating_add(1);
 
-        write!(self.buf, "{CSI}{row};{col}H").unwrap();
+        // A move along one axis is never longer than an absolute move, as the
+        // distance is less than the row or column. Only moves right or down
+        // are made though: after writing to the last column, the terminal's
+        // cursor stays there rather than moving past it, so a move left or up
+        // could be off by one.
+        match self.cursor {
+            Some(cursor) if cursor == pos => {}
+
+            Some(cursor) if cursor.y == pos.y && cursor.x < pos.x => {
+                write!(self.out(), "{CSI}{}C", pos.x - cursor.x).unwrap();
+            }
+
+            Some(cursor) if cursor.x == pos.x && cursor.y < pos.y => {
+                write!(self.out(), "{CSI}{}B", pos.y - cursor.y).unwrap();
+            }
+
+            _ => write!(self.out(), "{CSI}{row};{col}H").unwrap(),
+        }
+
+        self.cursor = Some(pos);
+    }
+
+    fn scroll_rows(&mut self, rows: Range<u16>, delta: i32) {
+        if rows.is_empty() || delta == 0 {
+            return;
+        }
+
+        // Set the scroll region, scroll it, then reset it to the whole screen.
+        write!(self.out(), "{CSI}{};{}r", rows.start + 1, rows.end).unwrap();
+        match delta {
+            1.. => write!(self.out(), "{CSI}{delta}S").unwrap(),
+            _ => write!(self.out(), "{CSI}{}T", delta.unsigned_abs()).unwrap(),
+        }
+        write!(self.out(), "{CSI}r").unwrap();
+
+        // Setting the scroll region moves the cursor home.
+        self.cursor = Some(OffsetU16::ZERO);
     }
 
     #[inline]
     fn set_cursor_vis(&mut self, vis: bool) {
         match vis {
-            true => write!(self.buf, "{CSI}?25h").unwrap(),
-            false => write!(self.buf, "{CSI}?25l").unwrap(),
+            true => write!(self.out(), "{CSI}?25h").unwrap(),
+            false => write!(self.out(), "{CSI}?25l").unwrap(),
         }
     }
 
     #[inline]
     fn set_cursor_shape(&mut self, shape: CursorShape) {
         match shape {
-            CursorShape::Block => write!(self.buf, "{CSI}2 q").unwrap(),
-            CursorShape::Underscore => write!(self.buf, "{CSI}4 q").unwrap(),
-            CursorShape::Bar => write!(self.buf, "{CSI}6 q").unwrap(),
+            CursorShape::Block => write!(self.out(), "{CSI}2 q").unwrap(),
+            CursorShape::Underscore => write!(self.out(), "{CSI}4 q").unwrap(),
+            CursorShape::Bar => write!(self.out(), "{CSI}6 q").unwrap(),
         }
     }
 
     #[inline]
     fn set_cursor_blinking(&mut self, blinking: bool) {
         match blinking {
-            true => write!(self.buf, "{CSI}?12h").unwrap(),
-            false => write!(self.buf, "{CSI}?12l").unwrap(),
+            true => write!(self.out(), "{CSI}?12h").unwrap(),
+            false => write!(self.out(), "{CSI}?12l").unwrap(),
+        }
+    }
+
+    #[inline]
+    fn set_focus_reporting(&mut self, enabled: bool) {
+        match enabled {
+            true => write!(self.out(), "{CSI}?1004h").unwrap(),
+            false => write!(self.out(), "{CSI}?1004l").unwrap(),
         }
     }
 
     #[inline]
     fn next_line(&mut self) {
-        self.buf.push('\n');
+        self.out().push('\n');
+        self.cursor = None;
     }
 
     #[inline]
     fn set_fg_color(&mut self, c: Color) {
-        write!(self.buf, "{CSI}3{}m", c as u8).unwrap();
+        write!(self.out(), "{CSI}3{}m", c as u8).unwrap();
     }
 
     #[inline]
     fn set_bg_color(&mut self, c: Color) {
-        write!(self.buf, "{CSI}4{}m", c as u8).unwrap();
+        write!(self.out(), "{CSI}4{}m", c as u8).unwrap();
     }
 
     #[inline]
     fn set_weight(&mut self, weight: Weight) {
         match weight {
-            Weight::Normal => write!(self.buf, "{CSI}22m").unwrap(),
-            Weight::Bold => write!(self.buf, "{CSI}1m").unwrap(),
-            Weight::Dim => write!(self.buf, "{CSI}2m").unwrap(),
+            Weight::Normal => write!(self.out(), "{CSI}22m").unwrap(),
+            Weight::Bold => write!(self.out(), "{CSI}1m").unwrap(),
+            Weight::Dim => write!(self.out(), "{CSI}2m").unwrap(),
         }
     }
 
     #[inline]
     fn set_underline(&mut self, underline: bool) {
         match underline {
-            true => write!(self.buf, "{CSI}4m").unwrap(),
-            false => write!(self.buf, "{CSI}24m").unwrap(),
+            true => write!(self.out(), "{CSI}4m").unwrap(),
+            false => write!(self.out(), "{CSI}24m").unwrap(),
         }
     }
 
+    #[inline]
+    fn set_link(&mut self, link: Option<&str>) {
+        let link = link.unwrap_or_default();
+        write!(self.out(), "{OSC}8;;{link}{ST}").unwrap();
+    }
+
     #[inline]
     fn write_str_raw(&mut self, s: &str) {
-        write!(self.buf, "{s}").unwrap();
+        write!(self.out(), "{s}").unwrap();
+
+        self.cursor = match self.cursor {
+            Some(cursor) if !s.chars().any(char::is_control) => {
+                let width = u16::try_from(s.width()).unwrap_or(u16::MAX);
+                Some(OffsetU16::new(cursor.x.saturating_add(width), cursor.y))
+            }
+            _ => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AnsiWriter;
+    use crate::platform::Writer;
+
+    fn moves(from: [u16; 2], to: [u16; 2]) -> String {
+        let mut w = AnsiWriter::new(vec![]);
+        w.set_cursor_pos(from);
+        w.flush().unwrap();
+
+        let start = w.inner().len();
+        w.set_cursor_pos(to);
+        w.flush().unwrap();
+
+        String::from_utf8(w.inner()[start..].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn relative_moves() {
+        assert_eq!(moves([10, 20], [13, 20]), "\x1b[3C");
+        assert_eq!(moves([10, 20], [10, 25]), "\x1b[5B");
+        assert_eq!(moves([10, 20], [10, 20]), "");
+    }
+
+    #[test]
+    fn absolute_moves() {
+        // Relative moves are only made right or down.
+        assert_eq!(moves([10, 20], [5, 20]), "\x1b[21;6H");
+        assert_eq!(moves([10, 20], [10, 15]), "\x1b[16;11H");
+        assert_eq!(moves([10, 20], [15, 25]), "\x1b[26;16H");
+    }
+
+    #[test]
+    fn scroll_rows() {
+        let mut w = AnsiWriter::new(vec![]);
+        w.scroll_rows(0..10, 2);
+        w.scroll_rows(3..5, -1);
+        w.scroll_rows(3..5, 0);
+        w.set_cursor_pos([0, 1]);
+        w.flush().unwrap();
+
+        assert_eq!(
+            w.inner().as_slice(),
+            "\x1b[1;10r\x1b[2S\x1b[r\x1b[4;5r\x1b[1T\x1b[r\x1b[1B".as_bytes()
+        );
+    }
+
+    #[test]
+    fn writing_moves_cursor() {
+        let mut w = AnsiWriter::new(vec![]);
+        w.set_cursor_home();
+        w.write_str_raw("ab日");
+        w.set_cursor_pos([6, 0]);
+        w.flush().unwrap();
+
+        assert_eq!(w.inner().as_slice(), "\x1b[Hab日\x1b[2C".as_bytes());
     }
 }
@@ -33,6 +33,12 @@ pub fn parse_event(bytes: &[u8]) -> Option<Event> {
                     modifiers: Modifiers::ALT,
                 }),
 
+                b"[Z" => Event::Key(KeyEvent::new_with_mods(KeyCode::Tab, Modifiers::SHIFT)),
+
+                // Focus reporting.
+                b"[I" => Event::FocusGained,
+                b"[O" => Event::FocusLost,
+
                 // VT sequence.
                 [b'[', rest @ .., b'~'] => {
                     let (key_code, modifiers) =
@@ -177,3 +183,38 @@ fn decode_byte(byte: u8) -> KeyEvent {
         _ => KeyEvent::new(KeyCode::Char(byte as char)),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_event;
+    use crate::event::{Event, KeyCode, KeyEvent, Modifiers};
+
+    #[test]
+    fn back_tab() {
+        assert!(matches!(
+            parse_event(b"\x1b[Z"),
+            Some(Event::Key(KeyEvent {
+                key_code: KeyCode::Tab,
+                modifiers: Modifiers::SHIFT,
+            }))
+        ));
+    }
+
+    #[test]
+    fn focus_events() {
+        assert!(matches!(parse_event(b"\x1b[I"), Some(Event::FocusGained)));
+        assert!(matches!(parse_event(b"\x1b[O"), Some(Event::FocusLost)));
+    }
+
+    #[test]
+    fn focus_events_not_keys() {
+        assert!(matches!(
+            parse_event(b"\x1b[A"),
+            Some(Event::Key(KeyEvent {
+                key_code: KeyCode::Up,
+                ..
+            }))
+        ));
+        assert!(parse_event(b"\x1b[X").is_none());
+    }
+}
@@ -71,10 +71,9 @@ impl PollingStdin {
         match self.recv.recv_deadline(deadline) {
             Ok(bytes) => bytes.map(Some),
             Err(RecvTimeoutError::Timeout) => Ok(None),
-            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
-                io::ErrorKind::Other,
-                "input thread disconnected",
-            )),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::other("input thread disconnected"))
+            }
         }
     }
 }
@@ -26,6 +26,7 @@ impl Terminal for LinuxTerminal {
         };
 
         term.writer().clear_all();
+        term.writer().set_focus_reporting(true);
         term.writer().flush()?;
 
         Ok(term)
@@ -52,6 +53,7 @@ impl Drop for LinuxTerminal {
         self.writer().set_cursor_home();
         self.writer().set_cursor_vis(true);
         self.writer().write_style(Style::default());
+        self.writer().set_focus_reporting(false);
 
         let _ = self.writer().flush();
     }
@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::mem::ManuallyDrop;
-use std::os::fd::{FromRawFd, RawFd};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{io, mem};
 
@@ -51,6 +51,13 @@ impl RawTerm {
             ));
         };
 
+        // Keys are read from stdin, so if it isn't the terminal, such as when
+        // text is piped in, point it at the terminal instead.
+        if unsafe { libc::isatty(STDIN_FILENO) } == 0 {
+            let tty = File::options().read(true).write(true).open("/dev/tty")?;
+            c_result!(unsafe { libc::dup2(tty.as_raw_fd(), STDIN_FILENO) })?;
+        }
+
         let mut termios = unsafe { get_termios(STDIN_FILENO)? };
         let termios_prev = termios;
 
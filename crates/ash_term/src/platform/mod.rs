@@ -1,11 +1,14 @@
 use std::io;
+use std::ops::Range;
 use std::time::Instant;
 
+use unicode_segmentation::UnicodeSegmentation;
+
 use crate::event::Event;
 use crate::style::{Color, CursorShape, CursorStyle, Style, Weight};
 use crate::units::OffsetU16;
 
-mod ansi;
+pub(crate) mod ansi;
 mod ansi_event;
 mod input;
 pub mod linux;
@@ -33,6 +36,11 @@ pub trait Writer {
     fn next_line(&mut self);
 
     fn set_cursor_pos(&mut self, poss: impl Into<OffsetU16>);
+
+    /// Scroll the text in `rows` by `delta` lines, leaving the rest of the
+    /// screen as it is. A positive `delta` moves the text up. The rows
+    /// scrolled in are blank, in the current background color.
+    fn scroll_rows(&mut self, rows: Range<u16>, delta: i32);
     fn set_cursor_vis(&mut self, vis: bool);
 
     fn set_cursor_shape(&mut self, shape: CursorShape);
@@ -44,16 +52,27 @@ pub trait Writer {
     fn set_weight(&mut self, weight: Weight);
     fn set_underline(&mut self, underline: bool);
 
+    /// Enable or disable focus reporting, so that the terminal sends
+    /// [`Event::FocusGained`] and [`Event::FocusLost`].
+    fn set_focus_reporting(&mut self, enabled: bool);
+
+    /// Start a hyperlink to `link`, or end the current hyperlink if `None`.
+    fn set_link(&mut self, link: Option<&str>);
+
     fn write_char(&mut self, ch: char) {
         if !ch.is_control() {
             self.write_str_raw(ch.encode_utf8(&mut [0; 4]));
         }
     }
 
+    /// Write a string, skipping any grapheme clusters that contain control
+    /// characters.
     #[inline]
     fn write_str(&mut self, s: &str) {
-        for part in s.split(char::is_control) {
-            self.write_str_raw(part);
+        for grapheme in s.graphemes(true) {
+            if !grapheme.chars().any(char::is_control) {
+                self.write_str_raw(grapheme);
+            }
         }
     }
 
@@ -77,3 +96,60 @@ pub trait Writer {
 pub trait Events {
     fn read_with_deadline(&mut self, deadline: Instant) -> io::Result<Option<Event>>;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::ansi::AnsiWriter;
+    use super::Writer;
+
+    #[test]
+    fn write_str_skips_control() {
+        let mut w = AnsiWriter::new(vec![]);
+
+        w.write_str("cafe\u{301}\x07!\r\nok");
+        w.flush().unwrap();
+
+        assert_eq!(w.inner().as_slice(), "cafe\u{301}!ok".as_bytes());
+    }
+
+    #[derive(Default)]
+    struct CountingWriter {
+        data: Vec<u8>,
+        writes: usize,
+        flushes: usize,
+    }
+
+    impl io::Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes += 1;
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flush_threshold() {
+        let mut w = AnsiWriter::new(CountingWriter::default()).with_flush_threshold(8);
+
+        w.write_str("hello");
+        w.set_cursor_home();
+        assert_eq!(w.inner().writes, 0);
+
+        // The buffer is past the threshold, so it's written out first.
+        w.write_str("world");
+        assert_eq!(w.inner().writes, 1);
+        assert_eq!(w.inner().data, b"hello\x1b[H");
+        assert_eq!(w.inner().flushes, 0);
+
+        w.flush().unwrap();
+        assert_eq!(w.inner().data, b"hello\x1b[Hworld");
+        assert_eq!(w.inner().flushes, 1);
+    }
+}
@@ -46,6 +46,28 @@ impl Style {
         weight: Weight::Normal,
         underline: false,
     };
+
+    /// Layer `other` over this style. Attributes that are set in `other`, that
+    /// is, not their default, override those in `self`.
+    #[must_use]
+    pub fn patch(self, other: Style) -> Style {
+        Style {
+            fg: match other.fg {
+                Color::Default => self.fg,
+                fg => fg,
+            },
+            bg: match other.bg {
+                Color::Default => self.bg,
+                bg => bg,
+            },
+
+            weight: match other.weight {
+                Weight::Normal => self.weight,
+                weight => weight,
+            },
+            underline: self.underline || other.underline,
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
@@ -60,3 +82,49 @@ impl CursorStyle {
         blinking: false,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Style, Weight};
+
+    #[test]
+    fn patch() {
+        let base = Style {
+            fg: Color::Red,
+            weight: Weight::Bold,
+            ..Style::EMPTY
+        };
+        let overlay = Style {
+            bg: Color::Blue,
+            underline: true,
+            ..Style::EMPTY
+        };
+
+        assert_eq!(
+            base.patch(overlay),
+            Style {
+                fg: Color::Red,
+                bg: Color::Blue,
+                weight: Weight::Bold,
+                underline: true,
+            }
+        );
+    }
+
+    #[test]
+    fn patch_overrides() {
+        let base = Style {
+            fg: Color::Red,
+            weight: Weight::Bold,
+            ..Style::EMPTY
+        };
+        let overlay = Style {
+            fg: Color::Green,
+            weight: Weight::Dim,
+            ..Style::EMPTY
+        };
+
+        assert_eq!(base.patch(overlay), overlay);
+        assert_eq!(base.patch(Style::EMPTY), base);
+    }
+}
@@ -1,188 +1,245 @@
 use std::ops::{Add, Div, Mul, Sub};
 
-macro_rules! vec2_type {
-    ($name:ident $t:ty) => {
-        #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
-        pub struct $name {
-            pub x: $t,
-            pub y: $t,
-        }
+/// A number that can be a component of a [`Vec2`].
+pub trait Num:
+    Copy + Ord + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self>
+{
+    const ZERO: Self;
+
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    fn saturating_mul(self, rhs: Self) -> Self;
+
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn checked_div(self, rhs: Self) -> Option<Self>;
+}
 
-        impl $name {
-            pub const ZERO: Self = Self::splat(0);
+macro_rules! impl_num {
+    ($($t:ty),*) => {
+        $(
+            impl Num for $t {
+                const ZERO: Self = 0;
 
-            #[inline]
-            pub const fn new(x: $t, y: $t) -> Self {
-                Self { x, y }
-            }
+                #[inline]
+                fn saturating_add(self, rhs: Self) -> Self {
+                    <$t>::saturating_add(self, rhs)
+                }
 
-            #[inline]
-            pub const fn splat(n: $t) -> Self {
-                Self::new(n, n)
-            }
+                #[inline]
+                fn saturating_sub(self, rhs: Self) -> Self {
+                    <$t>::saturating_sub(self, rhs)
+                }
 
-            #[inline]
-            #[must_use]
-            pub fn min(self, rhs: Self) -> Self {
-                self.join(rhs, <$t>::min)
-            }
+                #[inline]
+                fn saturating_mul(self, rhs: Self) -> Self {
+                    <$t>::saturating_mul(self, rhs)
+                }
 
-            #[inline]
-            #[must_use]
-            pub fn max(self, rhs: Self) -> Self {
-                self.join(rhs, <$t>::max)
-            }
+                #[inline]
+                fn checked_add(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_add(self, rhs)
+                }
 
-            #[inline]
-            #[must_use]
-            pub fn saturating_add(self, rhs: Self) -> Self {
-                self.join(rhs, <$t>::saturating_add)
-            }
+                #[inline]
+                fn checked_sub(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_sub(self, rhs)
+                }
 
-            #[inline]
-            #[must_use]
-            pub fn saturating_sub(self, rhs: Self) -> Self {
-                self.join(rhs, <$t>::saturating_sub)
-            }
+                #[inline]
+                fn checked_mul(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_mul(self, rhs)
+                }
 
-            #[inline]
-            #[must_use]
-            pub fn saturating_mul(self, rhs: Self) -> Self {
-                self.join(rhs, <$t>::saturating_mul)
+                #[inline]
+                fn checked_div(self, rhs: Self) -> Option<Self> {
+                    <$t>::checked_div(self, rhs)
+                }
             }
+        )*
+    };
+}
 
-            #[inline]
-            #[must_use]
-            pub fn checked_add(self, rhs: Self) -> Option<Self> {
-                self.try_join(rhs, <$t>::checked_add)
-            }
+impl_num! { u16, usize }
 
-            #[inline]
-            #[must_use]
-            pub fn checked_sub(self, rhs: Self) -> Option<Self> {
-                self.try_join(rhs, <$t>::checked_sub)
-            }
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Vec2<T> {
+    pub x: T,
+    pub y: T,
+}
 
-            #[inline]
-            #[must_use]
-            pub fn checked_mul(self, rhs: Self) -> Option<Self> {
-                self.try_join(rhs, <$t>::checked_mul)
-            }
+pub type OffsetU16 = Vec2<u16>;
+pub type OffsetUsize = Vec2<usize>;
 
-            #[inline]
-            #[must_use]
-            pub fn checked_div(self, rhs: Self) -> Option<Self> {
-                self.try_join(rhs, <$t>::checked_div)
-            }
+impl<T: Num> Vec2<T> {
+    pub const ZERO: Self = Self::splat(T::ZERO);
 
-            #[inline]
-            pub fn cmp_eq(&self, rhs: Self) -> Comparison {
-                self.cmp(rhs, <$t>::eq)
-            }
+    #[inline]
+    pub const fn new(x: T, y: T) -> Self {
+        Self { x, y }
+    }
 
-            #[inline]
-            pub fn cmp_ne(&self, rhs: Self) -> Comparison {
-                self.cmp(rhs, <$t>::ne)
-            }
+    #[inline]
+    pub const fn splat(n: T) -> Self {
+        Self::new(n, n)
+    }
 
-            #[inline]
-            pub fn cmp_lt(&self, rhs: Self) -> Comparison {
-                self.cmp(rhs, <$t>::lt)
-            }
+    #[inline]
+    #[must_use]
+    pub fn min(self, rhs: Self) -> Self {
+        self.join(rhs, Ord::min)
+    }
 
-            #[inline]
-            pub fn cmp_gt(&self, rhs: Self) -> Comparison {
-                self.cmp(rhs, <$t>::gt)
-            }
+    #[inline]
+    #[must_use]
+    pub fn max(self, rhs: Self) -> Self {
+        self.join(rhs, Ord::max)
+    }
 
-            #[inline]
-            pub fn cmp_le(&self, rhs: Self) -> Comparison {
-                self.cmp(rhs, <$t>::le)
-            }
+    #[inline]
+    #[must_use]
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        self.join(rhs, T::saturating_add)
+    }
 
-            #[inline]
-            pub fn cmp_ge(&self, rhs: Self) -> Comparison {
-                self.cmp(rhs, <$t>::ge)
-            }
+    #[inline]
+    #[must_use]
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        self.join(rhs, T::saturating_sub)
+    }
 
-            #[inline]
-            fn join(self, rhs: Self, f: impl Fn($t, $t) -> $t) -> Self {
-                Self::new(f(self.x, rhs.x), f(self.y, rhs.y))
-            }
+    #[inline]
+    #[must_use]
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        self.join(rhs, T::saturating_mul)
+    }
 
-            #[inline]
-            fn join_t(self, rhs: $t, f: impl Fn($t, $t) -> $t) -> Self {
-                Self::new(f(self.x, rhs), f(self.y, rhs))
-            }
+    #[inline]
+    #[must_use]
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.try_join(rhs, T::checked_add)
+    }
 
-            #[inline]
-            fn try_join(self, rhs: Self, f: impl Fn($t, $t) -> Option<$t>) -> Option<Self> {
-                Some(Self::new(f(self.x, rhs.x)?, f(self.y, rhs.y)?))
-            }
+    #[inline]
+    #[must_use]
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.try_join(rhs, T::checked_sub)
+    }
 
-            #[inline]
-            fn cmp(self, rhs: Self, f: impl Fn(&$t, &$t) -> bool) -> Comparison {
-                Comparison {
-                    x: f(&self.x, &rhs.x),
-                    y: f(&self.y, &rhs.y),
-                }
-            }
-        }
+    #[inline]
+    #[must_use]
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        self.try_join(rhs, T::checked_mul)
+    }
 
-        impl From<[$t; 2]> for $name {
-            #[inline]
-            fn from(value: [$t; 2]) -> Self {
-                Self::new(value[0], value[1])
-            }
-        }
+    #[inline]
+    #[must_use]
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        self.try_join(rhs, T::checked_div)
+    }
 
-        impl From<$name> for [$t; 2] {
-            #[inline]
-            fn from(value: $name) -> Self {
-                [value.x, value.y]
-            }
+    #[inline]
+    pub fn cmp_eq(&self, rhs: Self) -> Comparison {
+        self.cmp(rhs, T::eq)
+    }
+
+    #[inline]
+    pub fn cmp_ne(&self, rhs: Self) -> Comparison {
+        self.cmp(rhs, T::ne)
+    }
+
+    #[inline]
+    pub fn cmp_lt(&self, rhs: Self) -> Comparison {
+        self.cmp(rhs, T::lt)
+    }
+
+    #[inline]
+    pub fn cmp_gt(&self, rhs: Self) -> Comparison {
+        self.cmp(rhs, T::gt)
+    }
+
+    #[inline]
+    pub fn cmp_le(&self, rhs: Self) -> Comparison {
+        self.cmp(rhs, T::le)
+    }
+
+    #[inline]
+    pub fn cmp_ge(&self, rhs: Self) -> Comparison {
+        self.cmp(rhs, T::ge)
+    }
+
+    #[inline]
+    fn join(self, rhs: Self, f: impl Fn(T, T) -> T) -> Self {
+        Self::new(f(self.x, rhs.x), f(self.y, rhs.y))
+    }
+
+    #[inline]
+    fn join_t(self, rhs: T, f: impl Fn(T, T) -> T) -> Self {
+        Self::new(f(self.x, rhs), f(self.y, rhs))
+    }
+
+    #[inline]
+    fn try_join(self, rhs: Self, f: impl Fn(T, T) -> Option<T>) -> Option<Self> {
+        Some(Self::new(f(self.x, rhs.x)?, f(self.y, rhs.y)?))
+    }
+
+    #[inline]
+    fn cmp(self, rhs: Self, f: impl Fn(&T, &T) -> bool) -> Comparison {
+        Comparison {
+            x: f(&self.x, &rhs.x),
+            y: f(&self.y, &rhs.y),
         }
-    };
+    }
 }
 
-vec2_type! { OffsetU16 u16 }
-vec2_type! { OffsetUsize usize }
+impl<T> From<[T; 2]> for Vec2<T> {
+    #[inline]
+    fn from([x, y]: [T; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl<T> From<Vec2<T>> for [T; 2] {
+    #[inline]
+    fn from(value: Vec2<T>) -> Self {
+        [value.x, value.y]
+    }
+}
 
 macro_rules! impl_op_vec2 {
-    ($vec:ty, $t:ty = $($trait:ident $f:ident),*) => {
+    ($($trait:ident $f:ident),*) => {
         $(
-            impl $trait<$vec> for $vec {
+            impl<T: Num> $trait<Vec2<T>> for Vec2<T> {
                 type Output = Self;
 
                 #[inline]
                 fn $f(self, rhs: Self) -> Self::Output {
-                    self.join(rhs, <$t>::$f)
+                    self.join(rhs, T::$f)
                 }
             }
         )*
     };
 }
 
-impl_op_vec2! { OffsetU16, u16 = Add add, Sub sub }
-impl_op_vec2! { OffsetUsize, usize = Add add, Sub sub }
+impl_op_vec2! { Add add, Sub sub }
 
 macro_rules! impl_op_t {
-    ($vec:ty, $t:ty = $($trait:ident $f:ident),*) => {
+    ($($trait:ident $f:ident),*) => {
         $(
-            impl $trait<$t> for $vec {
+            impl<T: Num> $trait<T> for Vec2<T> {
                 type Output = Self;
 
                 #[inline]
-                fn $f(self, rhs: $t) -> Self::Output {
-                    self.join_t(rhs, <$t>::$f)
+                fn $f(self, rhs: T) -> Self::Output {
+                    self.join_t(rhs, T::$f)
                 }
             }
         )*
     };
 }
 
-impl_op_t! { OffsetU16, u16 = Add add, Sub sub, Mul mul, Div div }
-impl_op_t! { OffsetUsize, usize = Add add, Sub sub, Mul mul, Div div }
+impl_op_t! { Add add, Sub sub, Mul mul, Div div }
 
 impl OffsetU16 {
     #[inline]
@@ -194,9 +251,9 @@ impl OffsetU16 {
 macro_rules! conversions {
     ($($src:ty => $dest:ty),*) => {
         $(
-            impl From<$src> for $dest {
+            impl From<Vec2<$src>> for Vec2<$dest> {
                 #[inline]
-                fn from(value: $src) -> Self {
+                fn from(value: Vec2<$src>) -> Self {
                     Self::new(value.x as _, value.y as _)
                 }
             }
@@ -204,7 +261,76 @@ macro_rules! conversions {
     };
 }
 
-conversions! { OffsetU16 => OffsetUsize, OffsetUsize => OffsetU16 }
+conversions! { u16 => usize, usize => u16 }
+
+/// A rectangle of cells, from `start` (inclusive) to `end` (exclusive).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rect {
+    pub start: OffsetU16,
+    pub end: OffsetU16,
+}
+
+impl Rect {
+    pub const ZERO: Self = Self::new(OffsetU16::ZERO, OffsetU16::ZERO);
+
+    /// # Panics
+    /// Panics if `end` is less than `start`.
+    #[inline]
+    pub const fn new(start: OffsetU16, end: OffsetU16) -> Self {
+        assert!(start.x <= end.x && start.y <= end.y, "end is before start");
+        Self { start, end }
+    }
+
+    #[inline]
+    pub fn from_size(start: OffsetU16, size: OffsetU16) -> Self {
+        Self::new(start, start.saturating_add(size))
+    }
+
+    #[inline]
+    pub fn size(&self) -> OffsetU16 {
+        self.end - self.start
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.size().area() == 0
+    }
+
+    #[inline]
+    pub fn contains(&self, point: OffsetU16) -> bool {
+        point.cmp_ge(self.start).both() && point.cmp_lt(self.end).both()
+    }
+
+    /// The overlapping area of two rects. If they don't overlap, the result is
+    /// empty.
+    #[inline]
+    #[must_use]
+    pub fn intersect(&self, other: Self) -> Self {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end).max(start);
+        Self::new(start, end)
+    }
+
+    /// Split into left and right rects, `x` cells from the left edge.
+    #[inline]
+    pub fn split_horizontal(&self, x: u16) -> (Self, Self) {
+        let mid = self.start.x.saturating_add(x).min(self.end.x);
+        (
+            Self::new(self.start, OffsetU16::new(mid, self.end.y)),
+            Self::new(OffsetU16::new(mid, self.start.y), self.end),
+        )
+    }
+
+    /// Split into top and bottom rects, `y` cells from the top edge.
+    #[inline]
+    pub fn split_vertical(&self, y: u16) -> (Self, Self) {
+        let mid = self.start.y.saturating_add(y).min(self.end.y);
+        (
+            Self::new(self.start, OffsetU16::new(self.end.x, mid)),
+            Self::new(OffsetU16::new(self.start.x, mid), self.end),
+        )
+    }
+}
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Comparison {
@@ -223,3 +349,96 @@ impl Comparison {
         self.x || self.y
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Comparison, OffsetU16, OffsetUsize, Rect};
+
+    fn rect(start: [u16; 2], end: [u16; 2]) -> Rect {
+        Rect::new(start.into(), end.into())
+    }
+
+    #[test]
+    fn contains() {
+        let r = rect([1, 1], [3, 3]);
+        assert!(r.contains(OffsetU16::new(1, 1)));
+        assert!(r.contains(OffsetU16::new(2, 2)));
+        assert!(!r.contains(OffsetU16::new(3, 2)));
+        assert!(!r.contains(OffsetU16::new(0, 1)));
+    }
+
+    #[test]
+    fn intersect_overlapping() {
+        let a = rect([0, 0], [5, 5]);
+        let b = rect([3, 2], [8, 4]);
+        assert_eq!(a.intersect(b), rect([3, 2], [5, 4]));
+        assert_eq!(b.intersect(a), rect([3, 2], [5, 4]));
+    }
+
+    #[test]
+    fn intersect_disjoint() {
+        let a = rect([0, 0], [2, 2]);
+        let b = rect([4, 4], [6, 6]);
+        assert!(a.intersect(b).is_empty());
+        assert!(b.intersect(a).is_empty());
+    }
+
+    #[test]
+    fn split() {
+        let r = rect([2, 1], [10, 5]);
+
+        assert_eq!(
+            r.split_horizontal(3),
+            (rect([2, 1], [5, 5]), rect([5, 1], [10, 5]))
+        );
+        assert_eq!(
+            r.split_vertical(1),
+            (rect([2, 1], [10, 2]), rect([2, 2], [10, 5]))
+        );
+
+        // Out of range splits are clamped.
+        assert_eq!(r.split_horizontal(20), (r, rect([10, 1], [10, 5])));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = OffsetU16::new(3, 5);
+        let b = OffsetU16::new(1, 7);
+
+        assert_eq!(a + b, OffsetU16::new(4, 12));
+        assert_eq!(a * 2, OffsetU16::new(6, 10));
+        assert_eq!(a.saturating_sub(b), OffsetU16::new(2, 0));
+        assert_eq!(a.checked_sub(b), None);
+        assert_eq!(a.min(b), OffsetU16::new(1, 5));
+        assert_eq!(
+            OffsetU16::splat(u16::MAX).saturating_add(a),
+            OffsetU16::splat(u16::MAX)
+        );
+
+        let a = OffsetUsize::new(10, 20);
+        let b = OffsetUsize::new(4, 5);
+
+        assert_eq!(a - b, OffsetUsize::new(6, 15));
+        assert_eq!(a / 5, OffsetUsize::new(2, 4));
+        assert_eq!(a.checked_div(b), Some(OffsetUsize::new(2, 4)));
+        assert_eq!(a.checked_div(OffsetUsize::ZERO), None);
+        assert_eq!(a.max(b), a);
+    }
+
+    #[test]
+    fn comparisons() {
+        let a = OffsetU16::new(1, 5);
+        let b = OffsetU16::new(2, 5);
+
+        assert_eq!(a.cmp_lt(b), Comparison { x: true, y: false });
+        assert!(a.cmp_le(b).both());
+        assert!(!a.cmp_eq(b).both() && a.cmp_eq(b).either());
+
+        let a = OffsetUsize::from(a);
+        let b = OffsetUsize::from(b);
+        assert!(b.cmp_ge(a).both());
+        assert!(!b.cmp_gt(a).both());
+        assert_eq!(OffsetU16::from(b), OffsetU16::new(2, 5));
+        assert_eq!(<[usize; 2]>::from(b), [2, 5]);
+    }
+}